@@ -0,0 +1,550 @@
+/// Static metadata describing a command the server understands.
+pub struct CommandSpec {
+    pub name: &'static str,
+    /// Number of arguments including the command name. Negative values mean
+    /// "at least `-arity`", matching the convention of `COMMAND INFO`.
+    pub arity: i64,
+    /// Upper bound on the argument count for commands with a negative arity
+    /// but a fixed maximum, such as LPOP's optional count.
+    pub max_arity: Option<i64>,
+    /// Index from which the remaining arguments must come in pairs, such as
+    /// the field/value list of HSET.
+    pub pairs_from: Option<usize>,
+    pub flags: &'static [&'static str],
+    /// Position of the first and last key argument and the step between keys.
+    pub keys: (i64, i64, i64),
+    pub summary: &'static str,
+    pub since: &'static str,
+    pub group: &'static str,
+}
+
+pub const COMMANDS: &[CommandSpec] = &[
+    CommandSpec {
+        name: "acl",
+        arity: -2,
+        max_arity: None,
+        pairs_from: None,
+        flags: &["noscript", "loading", "stale"],
+        keys: (0, 0, 0),
+        summary: "A container for Access List Control commands.",
+        since: "6.0.0",
+        group: "server",
+    },
+    CommandSpec {
+        name: "append",
+        arity: 3,
+        max_arity: None,
+        pairs_from: None,
+        flags: &["write", "denyoom", "fast"],
+        keys: (1, 1, 1),
+        summary: "Appends a string to the value of a key. Creates the key if it doesn't exist.",
+        since: "2.0.0",
+        group: "string",
+    },
+    CommandSpec {
+        name: "blpop",
+        arity: -3,
+        max_arity: None,
+        pairs_from: None,
+        flags: &["write", "noscript", "blocking"],
+        keys: (1, -2, 1),
+        summary: "Removes and returns the first element in a list. \
+                  Blocks until an element is available otherwise. \
+                  Deletes the list if the last element was popped.",
+        since: "2.0.0",
+        group: "list",
+    },
+    CommandSpec {
+        name: "client",
+        arity: -2,
+        max_arity: None,
+        pairs_from: None,
+        flags: &["admin", "noscript", "loading", "stale"],
+        keys: (0, 0, 0),
+        summary: "A container for client connection commands.",
+        since: "2.4.0",
+        group: "connection",
+    },
+    CommandSpec {
+        name: "command",
+        arity: -1,
+        max_arity: None,
+        pairs_from: None,
+        flags: &["loading", "stale"],
+        keys: (0, 0, 0),
+        summary: "Returns detailed information about all commands.",
+        since: "2.8.13",
+        group: "server",
+    },
+    CommandSpec {
+        name: "dbsize",
+        arity: 1,
+        max_arity: None,
+        pairs_from: None,
+        flags: &["readonly", "fast"],
+        keys: (0, 0, 0),
+        summary: "Returns the number of keys in the database.",
+        since: "1.0.0",
+        group: "server",
+    },
+    CommandSpec {
+        name: "debug",
+        arity: -2,
+        max_arity: None,
+        pairs_from: None,
+        flags: &["admin", "noscript", "loading", "stale"],
+        keys: (0, 0, 0),
+        summary: "A container for debugging commands.",
+        since: "1.0.0",
+        group: "server",
+    },
+    CommandSpec {
+        name: "decr",
+        arity: 2,
+        max_arity: None,
+        pairs_from: None,
+        flags: &["write", "denyoom", "fast"],
+        keys: (1, 1, 1),
+        summary: "Decrements the integer value of a key by one. \
+                  Uses 0 as initial value if the key doesn't exist.",
+        since: "1.0.0",
+        group: "string",
+    },
+    CommandSpec {
+        name: "decrby",
+        arity: 3,
+        max_arity: None,
+        pairs_from: None,
+        flags: &["write", "denyoom", "fast"],
+        keys: (1, 1, 1),
+        summary: "Decrements a number from the integer value of a key. \
+                  Uses 0 as initial value if the key doesn't exist.",
+        since: "1.0.0",
+        group: "string",
+    },
+    CommandSpec {
+        name: "del",
+        arity: -2,
+        max_arity: None,
+        pairs_from: None,
+        flags: &["write"],
+        keys: (1, -1, 1),
+        summary: "Deletes one or more keys.",
+        since: "1.0.0",
+        group: "generic",
+    },
+    CommandSpec {
+        name: "echo",
+        arity: 2,
+        max_arity: None,
+        pairs_from: None,
+        flags: &["fast"],
+        keys: (0, 0, 0),
+        summary: "Returns the given string.",
+        since: "1.0.0",
+        group: "connection",
+    },
+    CommandSpec {
+        name: "exists",
+        arity: -2,
+        max_arity: None,
+        pairs_from: None,
+        flags: &["readonly", "fast"],
+        keys: (1, -1, 1),
+        summary: "Determines whether one or more keys exist.",
+        since: "1.0.0",
+        group: "generic",
+    },
+    CommandSpec {
+        name: "expire",
+        arity: -3,
+        max_arity: None,
+        pairs_from: None,
+        flags: &["write", "fast"],
+        keys: (1, 1, 1),
+        summary: "Sets the expiration time of a key in seconds.",
+        since: "1.0.0",
+        group: "generic",
+    },
+    CommandSpec {
+        name: "get",
+        arity: 2,
+        max_arity: None,
+        pairs_from: None,
+        flags: &["readonly", "fast"],
+        keys: (1, 1, 1),
+        summary: "Returns the string value of a key.",
+        since: "1.0.0",
+        group: "string",
+    },
+    CommandSpec {
+        name: "getrange",
+        arity: 4,
+        max_arity: None,
+        pairs_from: None,
+        flags: &["readonly"],
+        keys: (1, 1, 1),
+        summary: "Returns a substring of the string stored at a key.",
+        since: "2.4.0",
+        group: "string",
+    },
+    CommandSpec {
+        name: "getset",
+        arity: 3,
+        max_arity: None,
+        pairs_from: None,
+        flags: &["write", "denyoom", "fast"],
+        keys: (1, 1, 1),
+        summary: "Returns the previous string value of a key after setting it to a new value.",
+        since: "1.0.0",
+        group: "string",
+    },
+    CommandSpec {
+        name: "hdel",
+        arity: -3,
+        max_arity: None,
+        pairs_from: None,
+        flags: &["write", "fast"],
+        keys: (1, 1, 1),
+        summary: "Deletes one or more fields and their values from a hash. \
+                  Deletes the hash if no fields remain.",
+        since: "2.0.0",
+        group: "hash",
+    },
+    CommandSpec {
+        name: "hexists",
+        arity: 3,
+        max_arity: None,
+        pairs_from: None,
+        flags: &["readonly", "fast"],
+        keys: (1, 1, 1),
+        summary: "Determines whether a field exists in a hash.",
+        since: "2.0.0",
+        group: "hash",
+    },
+    CommandSpec {
+        name: "hget",
+        arity: 3,
+        max_arity: None,
+        pairs_from: None,
+        flags: &["readonly", "fast"],
+        keys: (1, 1, 1),
+        summary: "Returns the value of a field in a hash.",
+        since: "2.0.0",
+        group: "hash",
+    },
+    CommandSpec {
+        name: "hgetall",
+        arity: 2,
+        max_arity: None,
+        pairs_from: None,
+        flags: &["readonly"],
+        keys: (1, 1, 1),
+        summary: "Returns all fields and values in a hash.",
+        since: "2.0.0",
+        group: "hash",
+    },
+    CommandSpec {
+        name: "hlen",
+        arity: 2,
+        max_arity: None,
+        pairs_from: None,
+        flags: &["readonly", "fast"],
+        keys: (1, 1, 1),
+        summary: "Returns the number of fields in a hash.",
+        since: "2.0.0",
+        group: "hash",
+    },
+    CommandSpec {
+        name: "hset",
+        arity: -4,
+        max_arity: None,
+        pairs_from: Some(2),
+        flags: &["write", "denyoom", "fast"],
+        keys: (1, 1, 1),
+        summary: "Creates or modifies the value of a field in a hash.",
+        since: "2.0.0",
+        group: "hash",
+    },
+    CommandSpec {
+        name: "incr",
+        arity: 2,
+        max_arity: None,
+        pairs_from: None,
+        flags: &["write", "denyoom", "fast"],
+        keys: (1, 1, 1),
+        summary: "Increments the integer value of a key by one. \
+                  Uses 0 as initial value if the key doesn't exist.",
+        since: "1.0.0",
+        group: "string",
+    },
+    CommandSpec {
+        name: "incrby",
+        arity: 3,
+        max_arity: None,
+        pairs_from: None,
+        flags: &["write", "denyoom", "fast"],
+        keys: (1, 1, 1),
+        summary: "Increments the integer value of a key by a number. \
+                  Uses 0 as initial value if the key doesn't exist.",
+        since: "1.0.0",
+        group: "string",
+    },
+    CommandSpec {
+        name: "keys",
+        arity: 2,
+        max_arity: None,
+        pairs_from: None,
+        flags: &["readonly"],
+        keys: (0, 0, 0),
+        summary: "Returns all key names that match a pattern.",
+        since: "1.0.0",
+        group: "generic",
+    },
+    CommandSpec {
+        name: "llen",
+        arity: 2,
+        max_arity: None,
+        pairs_from: None,
+        flags: &["readonly", "fast"],
+        keys: (1, 1, 1),
+        summary: "Returns the length of a list.",
+        since: "1.0.0",
+        group: "list",
+    },
+    CommandSpec {
+        name: "lpop",
+        arity: -2,
+        max_arity: Some(3),
+        pairs_from: None,
+        flags: &["write", "fast"],
+        keys: (1, 1, 1),
+        summary: "Returns the first elements in a list after removing it. \
+                  Deletes the list if the last element was popped.",
+        since: "1.0.0",
+        group: "list",
+    },
+    CommandSpec {
+        name: "lpush",
+        arity: -3,
+        max_arity: None,
+        pairs_from: None,
+        flags: &["write", "denyoom", "fast"],
+        keys: (1, 1, 1),
+        summary: "Prepends one or more elements to a list. Creates the key if it doesn't exist.",
+        since: "1.0.0",
+        group: "list",
+    },
+    CommandSpec {
+        name: "lrange",
+        arity: 4,
+        max_arity: None,
+        pairs_from: None,
+        flags: &["readonly"],
+        keys: (1, 1, 1),
+        summary: "Returns a range of elements from a list.",
+        since: "1.0.0",
+        group: "list",
+    },
+    CommandSpec {
+        name: "memory",
+        arity: -2,
+        max_arity: None,
+        pairs_from: None,
+        flags: &["readonly"],
+        keys: (0, 0, 0),
+        summary: "A container for memory diagnostics commands.",
+        since: "4.0.0",
+        group: "server",
+    },
+    CommandSpec {
+        name: "mset",
+        arity: -3,
+        max_arity: None,
+        pairs_from: Some(1),
+        flags: &["write", "denyoom"],
+        keys: (1, -1, 2),
+        summary: "Atomically creates or modifies the string values of one or more keys.",
+        since: "1.0.1",
+        group: "string",
+    },
+    CommandSpec {
+        name: "object",
+        arity: -2,
+        max_arity: None,
+        pairs_from: None,
+        flags: &["readonly"],
+        keys: (2, 2, 1),
+        summary: "A container for object introspection commands.",
+        since: "2.2.3",
+        group: "generic",
+    },
+    CommandSpec {
+        name: "persist",
+        arity: 2,
+        max_arity: None,
+        pairs_from: None,
+        flags: &["write", "fast"],
+        keys: (1, 1, 1),
+        summary: "Removes the expiration time of a key.",
+        since: "2.2.0",
+        group: "generic",
+    },
+    CommandSpec {
+        name: "pexpire",
+        arity: -3,
+        max_arity: None,
+        pairs_from: None,
+        flags: &["write", "fast"],
+        keys: (1, 1, 1),
+        summary: "Sets the expiration time of a key in milliseconds.",
+        since: "2.6.0",
+        group: "generic",
+    },
+    CommandSpec {
+        name: "ping",
+        arity: -1,
+        max_arity: None,
+        pairs_from: None,
+        flags: &["fast"],
+        keys: (0, 0, 0),
+        summary: "Returns the server's liveliness response.",
+        since: "1.0.0",
+        group: "connection",
+    },
+    CommandSpec {
+        name: "pttl",
+        arity: 2,
+        max_arity: None,
+        pairs_from: None,
+        flags: &["readonly", "fast"],
+        keys: (1, 1, 1),
+        summary: "Returns the expiration time in milliseconds of a key.",
+        since: "2.6.0",
+        group: "generic",
+    },
+    CommandSpec {
+        name: "rpop",
+        arity: -2,
+        max_arity: Some(3),
+        pairs_from: None,
+        flags: &["write", "fast"],
+        keys: (1, 1, 1),
+        summary: "Returns and removes the last elements of the list. \
+                  Deletes the list if the last element was popped.",
+        since: "1.0.0",
+        group: "list",
+    },
+    CommandSpec {
+        name: "rpush",
+        arity: -3,
+        max_arity: None,
+        pairs_from: None,
+        flags: &["write", "denyoom", "fast"],
+        keys: (1, 1, 1),
+        summary: "Appends one or more elements to a list. Creates the key if it doesn't exist.",
+        since: "1.0.0",
+        group: "list",
+    },
+    CommandSpec {
+        name: "set",
+        arity: -3,
+        max_arity: None,
+        pairs_from: None,
+        flags: &["write", "denyoom"],
+        keys: (1, 1, 1),
+        summary: "Sets the string value of a key, ignoring its type.",
+        since: "1.0.0",
+        group: "string",
+    },
+    CommandSpec {
+        name: "setnx",
+        arity: 3,
+        max_arity: None,
+        pairs_from: None,
+        flags: &["write", "denyoom", "fast"],
+        keys: (1, 1, 1),
+        summary: "Set the string value of a key only when the key doesn't exist.",
+        since: "1.0.0",
+        group: "string",
+    },
+    CommandSpec {
+        name: "setrange",
+        arity: 4,
+        max_arity: None,
+        pairs_from: None,
+        flags: &["write", "denyoom"],
+        keys: (1, 1, 1),
+        summary: "Overwrites a part of a string value with another by an offset. \
+                  Creates the key if it doesn't exist.",
+        since: "2.2.0",
+        group: "string",
+    },
+    CommandSpec {
+        name: "shutdown",
+        arity: -1,
+        max_arity: None,
+        pairs_from: None,
+        flags: &["admin", "noscript", "loading", "stale"],
+        keys: (0, 0, 0),
+        summary: "Synchronously saves the database(s) to disk and shuts down the Redis server.",
+        since: "1.0.0",
+        group: "server",
+    },
+    CommandSpec {
+        name: "strlen",
+        arity: 2,
+        max_arity: None,
+        pairs_from: None,
+        flags: &["readonly", "fast"],
+        keys: (1, 1, 1),
+        summary: "Returns the length of a string value.",
+        since: "2.2.0",
+        group: "string",
+    },
+    CommandSpec {
+        name: "ttl",
+        arity: 2,
+        max_arity: None,
+        pairs_from: None,
+        flags: &["readonly", "fast"],
+        keys: (1, 1, 1),
+        summary: "Returns the expiration time in seconds of a key.",
+        since: "1.0.0",
+        group: "generic",
+    },
+    CommandSpec {
+        name: "type",
+        arity: 2,
+        max_arity: None,
+        pairs_from: None,
+        flags: &["readonly", "fast"],
+        keys: (1, 1, 1),
+        summary: "Determines the type of value stored at a key.",
+        since: "1.0.0",
+        group: "generic",
+    },
+];
+
+impl CommandSpec {
+    /// Whether `argc` arguments, including the command name, satisfy the arity.
+    pub fn accepts(&self, argc: usize) -> bool {
+        let argc = argc as i64;
+        let count_ok = if self.arity < 0 {
+            argc >= -self.arity && self.max_arity.map_or(true, |max| argc <= max)
+        } else {
+            argc == self.arity
+        };
+        let pairs_ok = self
+            .pairs_from
+            .map_or(true, |from| (argc - from as i64) % 2 == 0);
+        count_ok && pairs_ok
+    }
+}
+
+/// Look up a command by name, ignoring case.
+pub fn lookup(name: &str) -> Option<&'static CommandSpec> {
+    COMMANDS
+        .iter()
+        .find(|spec| spec.name.eq_ignore_ascii_case(name))
+}
@@ -0,0 +1,119 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::commands::{self, CommandSpec};
+use crate::errors::ConfigError;
+
+/// Server settings supplied on the command line as `--name value` options.
+pub struct Config {
+    /// TCP port to listen on, 6379 unless set with `--port`. Port 0 picks a
+    /// free one.
+    pub port: u16,
+    /// Maps a canonical command name to the name clients must send instead.
+    /// An empty replacement disables the command entirely.
+    pub renamed_commands: HashMap<String, String>,
+    /// Path of a Unix domain socket to accept connections on, besides TCP.
+    pub unixsocket: Option<PathBuf>,
+    /// How long a client may stay idle before it is disconnected. Set with
+    /// `--timeout <seconds>`, where 0 (the default) means never.
+    pub timeout: Option<Duration>,
+    /// Largest bulk string a client may send, in bytes. Set with
+    /// `--proto-max-bulk-len`; defaults to 512MB as in Redis.
+    pub proto_max_bulk_len: i64,
+    /// Whether commands flagged `admin`, such as DEBUG and SHUTDOWN, may run.
+    /// Set with `--enable-admin-commands yes|no`; defaults to yes.
+    pub enable_admin_commands: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            port: 6379,
+            renamed_commands: HashMap::new(),
+            unixsocket: None,
+            timeout: None,
+            proto_max_bulk_len: 512 * 1024 * 1024,
+            enable_admin_commands: true,
+        }
+    }
+}
+
+impl Config {
+    pub fn from_args(mut args: impl Iterator<Item = String>) -> Result<Config, ConfigError> {
+        let mut config = Config::default();
+        while let Some(option) = args.next() {
+            let mut value = || args.next().ok_or(ConfigError::MissingValue(option.clone()));
+            match option.as_str() {
+                "--rename-command" => {
+                    let from = value()?.to_lowercase();
+                    let to = value()?;
+                    if commands::lookup(&from).is_none() {
+                        return Err(ConfigError::UnknownCommand(from));
+                    }
+                    config.renamed_commands.insert(from, to);
+                }
+                "--port" => {
+                    config.port = value()?
+                        .parse()
+                        .map_err(|_| ConfigError::InvalidValue(option.clone()))?;
+                }
+                "--unixsocket" => config.unixsocket = Some(PathBuf::from(value()?)),
+                "--timeout" => {
+                    let seconds = value()?
+                        .parse::<u64>()
+                        .map_err(|_| ConfigError::InvalidValue(option.clone()))?;
+                    config.timeout = (seconds > 0).then(|| Duration::from_secs(seconds));
+                }
+                "--proto-max-bulk-len" => {
+                    config.proto_max_bulk_len = value()?
+                        .parse()
+                        .ok()
+                        .filter(|&len: &i64| len > 0)
+                        .ok_or_else(|| ConfigError::InvalidValue(option.clone()))?;
+                }
+                "--enable-admin-commands" => {
+                    config.enable_admin_commands = match value()?.as_str() {
+                        "yes" => true,
+                        "no" => false,
+                        _ => return Err(ConfigError::InvalidValue(option)),
+                    };
+                }
+                _ => return Err(ConfigError::UnknownOption(option)),
+            }
+        }
+        Ok(config)
+    }
+
+    /// The name clients send to run `spec`: its own unless renamed, or `None`
+    /// if it was renamed to "" and so disabled.
+    pub fn exposed_name(&self, spec: &'static CommandSpec) -> Option<&str> {
+        match self.renamed_commands.get(spec.name) {
+            Some(to) if to.is_empty() => None,
+            Some(to) => Some(to),
+            None => Some(spec.name),
+        }
+    }
+
+    /// Every enabled command, paired with the name clients invoke it by.
+    pub fn commands(&self) -> impl Iterator<Item = (&str, &'static CommandSpec)> + '_ {
+        commands::COMMANDS
+            .iter()
+            .filter_map(|spec| Some((self.exposed_name(spec)?, spec)))
+    }
+
+    /// Find the command a client invokes by sending `name`, honoring renames.
+    pub fn resolve(&self, name: &[u8]) -> Option<&'static CommandSpec> {
+        let renamed = self
+            .renamed_commands
+            .iter()
+            .find(|(_, to)| !to.is_empty() && to.as_bytes().eq_ignore_ascii_case(name));
+        match renamed {
+            Some((from, _)) => commands::lookup(from),
+            None => std::str::from_utf8(name)
+                .ok()
+                .and_then(commands::lookup)
+                .filter(|spec| !self.renamed_commands.contains_key(spec.name)),
+        }
+    }
+}
@@ -2,15 +2,18 @@ use std::fmt::{Debug, Display};
 
 #[derive(Debug)]
 pub enum RESPError {
-    UnexpectedEnd,
     UnknownStartingByte,
     IOError(std::io::Error),
     ParsingError(std::num::ParseIntError),
     IntParseFailure,
-    BadBulkStringSize(i64),
-    BadArraySize(i64),
+    BadBulkStringSize,
+    UnterminatedBulkString,
+    UnterminatedLine,
+    BadArraySize,
     InvalidCommand,
     InvalidArguments,
+    /// Not a failure: SHUTDOWN was called and the server should stop.
+    Shutdown,
 }
 
 impl From<std::io::Error> for RESPError {
@@ -28,17 +31,38 @@ impl From<std::num::ParseIntError> for RESPError {
 impl Display for RESPError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            RESPError::UnexpectedEnd => write!(f, "Unexpected end of input."),
             RESPError::UnknownStartingByte => write!(f, "Unknown starting byte."),
             RESPError::IOError(e) => write!(f, "{}", e),
             RESPError::ParsingError(e) => write!(f, "{}", e),
             RESPError::IntParseFailure => write!(f, "Failed to parse int."),
-            RESPError::BadBulkStringSize(size) => {
-                write!(f, "Invalid bulk string size of {} bytes.", size)
+            RESPError::BadBulkStringSize => write!(f, "Protocol error: invalid bulk length"),
+            RESPError::UnterminatedBulkString => {
+                write!(f, "Protocol error: expected '\\r\\n' after bulk string")
             }
-            RESPError::BadArraySize(size) => write!(f, "Invalid array size of {} bytes.", size),
+            RESPError::UnterminatedLine => write!(f, "Protocol error: expected '\\n' after '\\r'"),
+            RESPError::BadArraySize => write!(f, "Protocol error: invalid multibulk length"),
             RESPError::InvalidCommand => write!(f, "Invalid command."),
             RESPError::InvalidArguments => write!(f, "Invalid arguments."),
+            RESPError::Shutdown => write!(f, "Shutdown requested."),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    MissingValue(String),
+    InvalidValue(String),
+    UnknownOption(String),
+    UnknownCommand(String),
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::MissingValue(option) => write!(f, "Missing value for {}.", option),
+            ConfigError::InvalidValue(option) => write!(f, "Invalid value for {}.", option),
+            ConfigError::UnknownOption(option) => write!(f, "Unknown option {}.", option),
+            ConfigError::UnknownCommand(name) => write!(f, "Unknown command {}.", name),
         }
     }
 }
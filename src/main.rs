@@ -1,57 +1,216 @@
-use std::io::Read;
+use std::io::{ErrorKind, Read, Write};
 use std::net::{TcpListener, TcpStream};
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use std::thread;
+use std::time::Duration;
 
+mod commands;
+mod config;
 mod errors;
 mod parser;
+mod pause;
+#[cfg(test)]
+mod tests;
 
-use crate::parser::{parse, execute, RedisValue, KVStore};
+use crate::config::Config;
+use crate::errors::RESPError;
+use crate::parser::{parse, execute, RedisValue, Keyspace};
+use crate::pause::ClientPause;
 
 fn main() -> std::io::Result<()> {
-    let listener = TcpListener::bind("127.0.0.1:6379")?;
+    let config = match Config::from_args(std::env::args().skip(1)) {
+        Ok(config) => Arc::new(config),
+        Err(e) => {
+            println!("Error: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let store = Arc::new(Keyspace::default());
+    let pause = Arc::new(ClientPause::default());
+    // Nothing clears this in the binary, since SHUTDOWN exits the process.
+    let running = Arc::new(AtomicBool::new(true));
+
+    #[cfg(unix)]
+    if let Some(path) = &config.unixsocket {
+        // A stale socket file from a previous run would make bind fail.
+        let _ = std::fs::remove_file(path);
+        let listener = UnixListener::bind(path)?;
+        let config = Arc::clone(&config);
+        let store = Arc::clone(&store);
+        let pause = Arc::clone(&pause);
+        let running = Arc::clone(&running);
+        thread::spawn(move || serve(listener, &store, &pause, &config, &running));
+    }
+
+    let listener = listen(&config)?;
+    serve(listener, &store, &pause, &config, &running);
+    Ok(())
+}
+
+/// Bind the TCP port clients connect to.
+fn listen(config: &Config) -> std::io::Result<TcpListener> {
+    TcpListener::bind(("127.0.0.1", config.port))
+}
+
+/// A listening socket that `serve` can take clients from.
+trait Listener {
+    type Stream: Read + Write + Send + 'static;
+
+    /// Wait for the next client and give it the configured idle timeout.
+    fn next_client(&self, timeout: Option<Duration>) -> std::io::Result<Self::Stream>;
+}
+
+impl Listener for TcpListener {
+    type Stream = TcpStream;
+
+    fn next_client(&self, timeout: Option<Duration>) -> std::io::Result<TcpStream> {
+        let (stream, _) = self.accept()?;
+        stream.set_read_timeout(timeout)?;
+        Ok(stream)
+    }
+}
+
+#[cfg(unix)]
+impl Listener for UnixListener {
+    type Stream = UnixStream;
 
-    for stream in listener.incoming() {
-        match stream {
+    fn next_client(&self, timeout: Option<Duration>) -> std::io::Result<UnixStream> {
+        let (stream, _) = self.accept()?;
+        stream.set_read_timeout(timeout)?;
+        Ok(stream)
+    }
+}
+
+/// Serve every client of `listener` on its own thread until `running` is
+/// cleared, then wait for the open connections to close.
+///
+/// The flag is read after each accept, so whoever clears it must connect once
+/// more to wake the loop.
+fn serve<L: Listener>(
+    listener: L,
+    store: &Arc<Keyspace>,
+    pause: &Arc<ClientPause>,
+    config: &Arc<Config>,
+    running: &AtomicBool,
+) {
+    let mut connections: Vec<thread::JoinHandle<()>> = Vec::new();
+    loop {
+        let client = listener.next_client(config.timeout);
+        if !running.load(Ordering::SeqCst) {
+            break;
+        }
+        match client {
             Ok(stream) => {
                 println!("accepted new connection");
-                thread::spawn(move || handle_connection(stream));
+                let store = Arc::clone(store);
+                let pause = Arc::clone(pause);
+                let config = Arc::clone(config);
+                connections.retain(|connection| !connection.is_finished());
+                connections.push(thread::spawn(move || {
+                    if handle_connection(stream, &store, &pause, &config) {
+                        shutdown(&config);
+                    }
+                }));
             }
             Err(e) => {
                 println!("error: {}", e);
             }
         }
     }
-    Ok(())
+    for connection in connections {
+        let _ = connection.join();
+    }
+}
+
+/// Stop the server once SHUTDOWN has been answered, removing the Unix socket
+/// file so the next start doesn't find it.
+fn shutdown(config: &Config) -> ! {
+    println!("shutting down");
+    if let Some(path) = &config.unixsocket {
+        let _ = std::fs::remove_file(path);
+    }
+    std::process::exit(0);
 }
 
-fn handle_connection(mut stream: TcpStream) {
+/// Serve one client until it disconnects. Returns whether it asked the
+/// server to shut down, in which case every earlier reply has been written.
+fn handle_connection<S: Read + Write>(
+    mut stream: S,
+    store: &Keyspace,
+    pause: &ClientPause,
+    config: &Config,
+) -> bool {
     let mut buf = vec![0; 512];
-    let mut store = KVStore::new();
+    let mut pending = Vec::new();
     loop {
-        let n = stream
-            .read(buf.as_mut_slice())
-            .expect("Failed to read from stream.");
+        let n = match stream.read(buf.as_mut_slice()) {
+            Ok(n) => n,
+            Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                println!("closing idle client");
+                break;
+            }
+            Err(e) => {
+                println!("Error: {}", e);
+                break;
+            }
+        };
         println!("received {} bytes", n);
 
         if n == 0 {
             break;
         }
+        pending.extend_from_slice(&buf[..n]);
 
-        let args = match parse(buf.as_slice(), 0) {
-            Ok(Some((_pos, RedisValue::Array(vec)))) => vec,
-            Err(e) => {
-                println!("Error: {}", e);
-                break;
+        // Run every complete command received so far and collect the replies, so
+        // a pipelined batch goes out in one write once the input is drained.
+        let mut replies = Vec::new();
+        let mut pos = 0;
+        let mut closing = false;
+        let mut shutting_down = false;
+        while !closing {
+            match parse(&pending, pos, config.proto_max_bulk_len) {
+                Ok(Some((next, RedisValue::Array(args)))) => {
+                    pos = next;
+                    // Wait out any CLIENT PAUSE before taking the lock, so commands
+                    // the pause doesn't cover keep running meanwhile.
+                    if let Some(spec) = args.first().and_then(|name| match name {
+                        RedisValue::String(name) => config.resolve(name),
+                        _ => None,
+                    }) {
+                        pause.wait(spec);
+                    }
+                    match execute(&mut replies, &args, store, pause, config) {
+                        Ok(()) => {}
+                        Err(RESPError::Shutdown) => {
+                            shutting_down = true;
+                            closing = true;
+                        }
+                        Err(e) => {
+                            println!("Error: {}", e);
+                            closing = true;
+                        }
+                    }
+                }
+                Ok(None) => break,
+                Err(e) => {
+                    println!("Error: {}", e);
+                    replies.extend_from_slice(format!("-ERR {}\r\n", e).as_bytes());
+                    closing = true;
+                }
+                Ok(Some(_)) => {
+                    println!("Invalid message");
+                    closing = true;
+                }
             }
-            _ => {
-                println!("Invalid message");
-                break;
-            }
-        };
+        }
+        pending.drain(..pos);
 
-        if let Err(e) = execute(&mut stream, &args, &mut store) {
-            println!("Error: {}", e);
-            break;
+        if stream.write_all(&replies).is_err() || closing {
+            return shutting_down;
         }
     }
+    false
 }
@@ -1,20 +1,93 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::io::Write;
-use std::net::TcpStream;
 use std::str::from_utf8;
-use std::time::{Duration, Instant};
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
+use crate::commands::CommandSpec;
+use crate::config::Config;
 use crate::errors::RESPError;
+use crate::pause::ClientPause;
 
 const NULL: &[u8] = b"$-1\r\n";
 const OK: &[u8] = b"+OK\r\n";
+const SYNTAX_ERROR: &[u8] = b"-ERR syntax error\r\n";
+const NOT_AN_INTEGER: &[u8] = b"-ERR value is not an integer or out of range\r\n";
+const OVERFLOW: &[u8] = b"-ERR increment or decrement would overflow\r\n";
+const WRONGTYPE: &[u8] = b"-WRONGTYPE Operation against a key holding the wrong kind of value\r\n";
+const INVALID_SET_EXPIRE: &[u8] = b"-ERR invalid expire time in 'set' command\r\n";
+const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+/// Most elements a client may declare for one array, as in Redis.
+const MAX_MULTIBULK_LEN: i64 = 1024 * 1024;
+/// Longest string Redis stores inline with its object header.
+const EMBSTR_MAX_LEN: usize = 44;
+const DEFAULT_USER: &str = "default";
+const DEFAULT_USER_RULES: &str = "on nopass sanitize-payload ~* &* +@all";
+const MEMORY_DOCTOR_REPORT: &str = "Hi Sam, I can't find any memory issue in your instance. \
+I can only account for what occurs on this base.";
 
-pub type KVStore = HashMap<String, (String, Option<Expiry>)>;
+pub type KVStore = HashMap<Vec<u8>, Entry>;
+/// A stored value and its TTL, if any.
+pub type Entry = (StoredValue, Option<Expiry>);
+/// The field/value pairs of a hash.
+type Fields = HashMap<Vec<u8>, Vec<u8>>;
 type RedisResult = Result<Option<(usize, RedisValue)>, RESPError>;
 
+/// The store shared by every connection.
+#[derive(Default)]
+pub struct Keyspace {
+    pub entries: Mutex<KVStore>,
+    /// Signalled after every list push, so blocked pops re-check their keys.
+    pub pushed: Condvar,
+}
+
+/// A value held in the store, tagged with its Redis data type.
+pub enum StoredValue {
+    /// A string and the encoding Redis would keep it in.
+    Str(Vec<u8>, StrEncoding),
+    List(VecDeque<Vec<u8>>),
+    Hash(Fields),
+}
+
+/// How Redis represents a string internally, as OBJECT ENCODING reports it.
+pub enum StrEncoding {
+    Int,
+    Embstr,
+    Raw,
+}
+
 pub struct Expiry(Instant, Duration);
 struct BufSplit(usize, usize);
 
+impl Expiry {
+    /// Whether the key this expiry is attached to should now read as absent.
+    fn has_elapsed(&self) -> bool {
+        self.0.elapsed() >= self.1
+    }
+
+    /// Time left before the key expires, never negative.
+    fn remaining(&self) -> Duration {
+        self.1.saturating_sub(self.0.elapsed())
+    }
+}
+
+impl StoredValue {
+    /// Wrap a string written whole, as SET does. Canonical 64-bit integers
+    /// are `int`, short strings `embstr` and the rest `raw`.
+    fn string(value: Vec<u8>) -> StoredValue {
+        let canonical = parse_integer(&value).is_some_and(|i| i.to_string().into_bytes() == value);
+        let encoding = if canonical {
+            StrEncoding::Int
+        } else if value.len() <= EMBSTR_MAX_LEN {
+            StrEncoding::Embstr
+        } else {
+            StrEncoding::Raw
+        };
+        StoredValue::Str(value, encoding)
+    }
+}
+
 impl BufSplit {
     /// Get a lifetime appropriate slice of the underlying buffer.
     ///
@@ -27,7 +100,8 @@ impl BufSplit {
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum RedisValue {
-    String(String),
+    /// A bulk or simple string. Either may hold arbitrary bytes.
+    String(Vec<u8>),
     Error(String),
     Int(i64),
     Array(Vec<RedisValue>),
@@ -35,13 +109,40 @@ pub enum RedisValue {
     NullBulkString,
 }
 
-pub fn parse(buf: &[u8], pos: usize) -> RedisResult {
-    if buf.is_empty() {
+impl RedisValue {
+    /// Serialize as a reply, encoding `String` as a bulk string.
+    pub fn encode(&self) -> Vec<u8> {
+        match self {
+            RedisValue::String(bytes) => {
+                let mut out = format!("${}\r\n", bytes.len()).into_bytes();
+                out.extend_from_slice(bytes);
+                out.extend_from_slice(b"\r\n");
+                out
+            }
+            RedisValue::Error(err) => format!("-{}\r\n", err).into_bytes(),
+            RedisValue::Int(i) => format!(":{}\r\n", i).into_bytes(),
+            RedisValue::Array(vec) => {
+                let mut out = format!("*{}\r\n", vec.len()).into_bytes();
+                for value in vec {
+                    out.extend(value.encode());
+                }
+                out
+            }
+            RedisValue::NullArray => b"*-1\r\n".to_vec(),
+            RedisValue::NullBulkString => NULL.to_vec(),
+        }
+    }
+}
+
+/// Parse one frame starting at `pos`, rejecting bulk strings declared longer
+/// than `max_bulk_len` bytes.
+pub fn parse(buf: &[u8], pos: usize, max_bulk_len: i64) -> RedisResult {
+    if buf.len() <= pos {
         return Ok(None);
     }
     match buf[pos] {
-        b'*' => array(buf, pos + 1),
-        b'$' => bulk_string(buf, pos + 1),
+        b'*' => array(buf, pos + 1, max_bulk_len),
+        b'$' => bulk_string(buf, pos + 1, max_bulk_len),
         b'+' => simple_string(buf, pos + 1),
         b'-' => error(buf, pos + 1),
         b':' => redis_int(buf, pos + 1),
@@ -50,9 +151,11 @@ pub fn parse(buf: &[u8], pos: usize) -> RedisResult {
 }
 
 pub fn execute(
-    stream: &mut TcpStream,
+    stream: &mut impl Write,
     msg: &[RedisValue],
-    store: &mut KVStore,
+    keyspace: &Keyspace,
+    pause: &ClientPause,
+    config: &Config,
 ) -> Result<(), RESPError> {
     if msg.is_empty() {
         return Err(RESPError::UnknownStartingByte);
@@ -63,78 +166,919 @@ pub fn execute(
         _ => return Err(RESPError::InvalidCommand),
     };
 
-    match cmd.to_lowercase().as_str() {
+    // Unknown, renamed-away and disabled commands all get the same reply, and
+    // the connection stays open.
+    let spec = match config.resolve(cmd) {
+        Some(spec) => spec,
+        None => {
+            stream.write_all(
+                format!(
+                    "-ERR unknown command '{}'\r\n",
+                    String::from_utf8_lossy(cmd)
+                )
+                .as_bytes(),
+            )?;
+            return Ok(());
+        }
+    };
+
+    if !spec.accepts(msg.len()) {
+        stream.write_all(
+            format!(
+                "-ERR wrong number of arguments for '{}' command\r\n",
+                spec.name
+            )
+            .as_bytes(),
+        )?;
+        return Ok(());
+    }
+
+    if spec.flags.contains(&"admin") && !config.enable_admin_commands {
+        stream.write_all(
+            format!(
+                "-NOPERM User {} has no permissions to run the '{}' command\r\n",
+                DEFAULT_USER, spec.name
+            )
+            .as_bytes(),
+        )?;
+        return Ok(());
+    }
+
+    // The lock is held for a single command so clients interleave freely.
+    let mut guard = keyspace.entries.lock().unwrap();
+    let store = &mut *guard;
+    match spec.name {
         "ping" => stream.write_all(b"+PONG\r\n")?,
         "echo" => {
-            if msg.len() < 2 {
-                return Err(RESPError::InvalidArguments);
-            }
-            if let Some(RedisValue::String(str)) = msg.get(1) {
-                let length = str.len();
-                stream.write_all(format!("${}\r\n{}\r\n", length, str).as_bytes())?;
+            if let Some(message @ RedisValue::String(_)) = msg.get(1) {
+                stream.write_all(&message.encode())?;
             }
         }
         "get" => {
-            if msg.len() < 2 {
-                return Err(RESPError::InvalidArguments);
-            }
             let key = match msg.get(1).unwrap() {
                 RedisValue::String(key) => key,
                 _ => return Err(RESPError::InvalidArguments),
             };
-            match store.get(key) {
-                Some((value, None)) => {
-                    let len = value.len();
-                    stream.write_all(format!("${}\r\n{}\r\n", len, value).as_bytes())?;
+            remove_if_expired(store, key);
+            match get_str(store, key) {
+                Ok(Some(value)) => {
+                    stream.write_all(&RedisValue::String(value.to_owned()).encode())?
+                }
+                Ok(None) => stream.write_all(NULL)?,
+                Err(reply) => stream.write_all(reply)?,
+            }
+        }
+        "set" => {
+            let key = string_arg(msg, 1)?;
+            let value = string_arg(msg, 2)?;
+            // Options are validated in full before the store is touched.
+            let mut expiry = None;
+            // Some(true) for XX (key must exist), Some(false) for NX (key must not).
+            let mut must_exist = None;
+            let mut keep_ttl = false;
+            let mut get = false;
+            let mut i = 3;
+            while i < msg.len() {
+                let option = keyword(msg, i)?;
+                match option.as_str() {
+                    "ex" | "px" | "exat" | "pxat"
+                        if expiry.is_none() && !keep_ttl && i + 1 < msg.len() =>
+                    {
+                        let Some(amount) = parse_integer(string_arg(msg, i + 1)?) else {
+                            stream.write_all(NOT_AN_INTEGER)?;
+                            return Ok(());
+                        };
+                        let unit = if option.starts_with("ex") { 1000 } else { 1 };
+                        let Some(millis) = amount.checked_mul(unit).filter(|&millis| millis > 0)
+                        else {
+                            stream.write_all(INVALID_SET_EXPIRE)?;
+                            return Ok(());
+                        };
+                        expiry = Some(if option.ends_with("at") {
+                            until_unix_millis(millis)
+                        } else {
+                            Duration::from_millis(millis as u64)
+                        });
+                        i += 2;
+                    }
+                    "keepttl" if expiry.is_none() => {
+                        keep_ttl = true;
+                        i += 1;
+                    }
+                    "get" => {
+                        get = true;
+                        i += 1;
+                    }
+                    "nx" | "xx" if must_exist.map_or(true, |xx| xx == (option == "xx")) => {
+                        must_exist = Some(option == "xx");
+                        i += 1;
+                    }
+                    _ => {
+                        stream.write_all(SYNTAX_ERROR)?;
+                        return Ok(());
+                    }
+                }
+            }
+            remove_if_expired(store, key);
+            let old = match get.then(|| get_str(store, key)) {
+                None => None,
+                Some(Ok(old)) => Some(old.map_or(RedisValue::NullBulkString, |old| {
+                    RedisValue::String(old.to_owned())
+                })),
+                Some(Err(reply)) => {
+                    stream.write_all(reply)?;
+                    return Ok(());
+                }
+            };
+            if must_exist.is_some_and(|xx| xx != store.contains_key(key)) {
+                stream.write_all(&old.map_or(NULL.to_vec(), |old| old.encode()))?;
+                return Ok(());
+            }
+            let expiry = if keep_ttl {
+                store.remove(key).and_then(|(_, expiry)| expiry)
+            } else {
+                expiry.map(|duration| Expiry(Instant::now(), duration))
+            };
+            store.insert(
+                key.to_owned(),
+                (StoredValue::string(value.to_owned()), expiry),
+            );
+            match old {
+                Some(old) => stream.write_all(&old.encode())?,
+                None => stream.write_all(OK)?,
+            }
+        }
+        "mset" => {
+            // Like SET, each write replaces the whole entry and clears any TTL.
+            for i in (1..msg.len()).step_by(2) {
+                let key = string_arg(msg, i)?;
+                let value = string_arg(msg, i + 1)?;
+                store.insert(
+                    key.to_owned(),
+                    (StoredValue::string(value.to_owned()), None),
+                );
+            }
+            stream.write_all(OK)?;
+        }
+        "del" => {
+            let mut deleted = 0;
+            for i in 1..msg.len() {
+                let key = string_arg(msg, i)?;
+                remove_if_expired(store, key);
+                if store.remove(key).is_some() {
+                    deleted += 1;
+                }
+            }
+            stream.write_all(format!(":{}\r\n", deleted).as_bytes())?;
+        }
+        "exists" => {
+            let mut found = 0;
+            for i in 1..msg.len() {
+                let key = string_arg(msg, i)?;
+                remove_if_expired(store, key);
+                if store.contains_key(key) {
+                    found += 1;
+                }
+            }
+            stream.write_all(format!(":{}\r\n", found).as_bytes())?;
+        }
+        "incr" | "decr" => {
+            let key = string_arg(msg, 1)?;
+            let delta = if spec.name == "incr" { 1 } else { -1 };
+            match increment(store, key, delta) {
+                Ok(value) => stream.write_all(format!(":{}\r\n", value).as_bytes())?,
+                Err(reply) => stream.write_all(reply)?,
+            }
+        }
+        "incrby" | "decrby" => {
+            let key = string_arg(msg, 1)?;
+            let Some(delta) = parse_integer(string_arg(msg, 2)?) else {
+                stream.write_all(NOT_AN_INTEGER)?;
+                return Ok(());
+            };
+            let delta = if spec.name == "incrby" {
+                Some(delta)
+            } else {
+                delta.checked_neg()
+            };
+            match delta
+                .ok_or(OVERFLOW)
+                .and_then(|delta| increment(store, key, delta))
+            {
+                Ok(value) => stream.write_all(format!(":{}\r\n", value).as_bytes())?,
+                Err(reply) => stream.write_all(reply)?,
+            }
+        }
+        "append" => {
+            let key = string_arg(msg, 1)?;
+            let suffix = string_arg(msg, 2)?;
+            remove_if_expired(store, key);
+            // A new key is stored as SET would store the suffix.
+            if !store.contains_key(key) {
+                store.insert(
+                    key.to_owned(),
+                    (StoredValue::string(suffix.to_owned()), None),
+                );
+                stream.write_all(format!(":{}\r\n", suffix.len()).as_bytes())?;
+                return Ok(());
+            }
+            // Appending in place keeps any TTL.
+            let value = match str_entry(store, key) {
+                Ok(value) => value,
+                Err(reply) => {
+                    stream.write_all(reply)?;
+                    return Ok(());
+                }
+            };
+            value.extend_from_slice(suffix);
+            stream.write_all(format!(":{}\r\n", value.len()).as_bytes())?;
+        }
+        "keys" => {
+            let pattern = string_arg(msg, 1)?;
+            let keys = store
+                .iter()
+                .filter(|(_, (_, expiry))| !expiry.as_ref().is_some_and(Expiry::has_elapsed))
+                .filter(|(key, _)| glob_match(pattern, key))
+                .map(|(key, _)| RedisValue::String(key.to_owned()))
+                .collect();
+            stream.write_all(&RedisValue::Array(keys).encode())?;
+        }
+        "dbsize" => {
+            let live = store
+                .values()
+                .filter(|(_, expiry)| !expiry.as_ref().is_some_and(Expiry::has_elapsed))
+                .count();
+            stream.write_all(format!(":{}\r\n", live).as_bytes())?;
+        }
+        "strlen" => {
+            let key = string_arg(msg, 1)?;
+            remove_if_expired(store, key);
+            match get_str(store, key) {
+                Ok(value) => {
+                    let len = value.map_or(0, Vec::len);
+                    stream.write_all(format!(":{}\r\n", len).as_bytes())?;
+                }
+                Err(reply) => stream.write_all(reply)?,
+            }
+        }
+        "getset" => {
+            let key = string_arg(msg, 1)?;
+            let value = string_arg(msg, 2)?;
+            remove_if_expired(store, key);
+            let old = match get_str(store, key) {
+                Ok(old) => old.map_or(RedisValue::NullBulkString, |old| {
+                    RedisValue::String(old.to_owned())
+                }),
+                Err(reply) => {
+                    stream.write_all(reply)?;
+                    return Ok(());
                 }
-                Some((value, Some(Expiry(start, duration)))) => {
-                    if start.elapsed() < *duration {
-                        let len = value.len();
-                        stream.write_all(format!("${}\r\n{}\r\n", len, value).as_bytes())?;
+            };
+            // Like SET, this replaces the whole entry and so clears any TTL.
+            store.insert(
+                key.to_owned(),
+                (StoredValue::string(value.to_owned()), None),
+            );
+            stream.write_all(&old.encode())?;
+        }
+        "setnx" => {
+            let key = string_arg(msg, 1)?;
+            let value = string_arg(msg, 2)?;
+            remove_if_expired(store, key);
+            if store.contains_key(key) {
+                stream.write_all(b":0\r\n")?;
+            } else {
+                store.insert(
+                    key.to_owned(),
+                    (StoredValue::string(value.to_owned()), None),
+                );
+                stream.write_all(b":1\r\n")?;
+            }
+        }
+        "ttl" | "pttl" => {
+            let key = string_arg(msg, 1)?;
+            remove_if_expired(store, key);
+            let ttl = match store.get(key) {
+                None => -2,
+                Some((_, None)) => -1,
+                Some((_, Some(expiry))) => {
+                    let millis = expiry.remaining().as_millis() as i64;
+                    // TTL rounds to the nearest second, as Redis does.
+                    if spec.name == "ttl" {
+                        (millis + 500) / 1000
                     } else {
-                        stream.write_all(NULL)?;
+                        millis
+                    }
+                }
+            };
+            stream.write_all(format!(":{}\r\n", ttl).as_bytes())?;
+        }
+        "expire" | "pexpire" => {
+            let key = string_arg(msg, 1)?;
+            let Some(amount) = parse_integer(string_arg(msg, 2)?) else {
+                stream.write_all(NOT_AN_INTEGER)?;
+                return Ok(());
+            };
+            let unit = if spec.name == "expire" { 1000 } else { 1 };
+            let Some(millis) = amount.checked_mul(unit).filter(|&millis| millis > 0) else {
+                let reply = format!("-ERR invalid expire time in '{}' command\r\n", spec.name);
+                stream.write_all(reply.as_bytes())?;
+                return Ok(());
+            };
+            let (mut nx, mut xx, mut gt, mut lt) = (false, false, false, false);
+            for i in 3..msg.len() {
+                match keyword(msg, i)?.as_str() {
+                    "nx" => nx = true,
+                    "xx" => xx = true,
+                    "gt" => gt = true,
+                    "lt" => lt = true,
+                    _ => {
+                        let option = String::from_utf8_lossy(string_arg(msg, i)?);
+                        let reply = format!("-ERR Unsupported option {}\r\n", option);
+                        stream.write_all(reply.as_bytes())?;
+                        return Ok(());
                     }
                 }
-                None => stream.write_all(NULL)?,
+            }
+            if nx && (xx || gt || lt) {
+                stream.write_all(
+                    b"-ERR NX and XX, GT or LT options at the same time are not compatible\r\n",
+                )?;
+                return Ok(());
+            }
+            if gt && lt {
+                stream
+                    .write_all(b"-ERR GT and LT options at the same time are not compatible\r\n")?;
+                return Ok(());
+            }
+            remove_if_expired(store, key);
+            let Some((_, expiry)) = store.get_mut(key) else {
+                stream.write_all(b":0\r\n")?;
+                return Ok(());
+            };
+            let updated = Duration::from_millis(millis as u64);
+            let current = expiry.as_ref().map(Expiry::remaining);
+            // A key without a TTL never expires, so GT fails on it and LT passes.
+            let allowed = (!nx || current.is_none())
+                && (!xx || current.is_some())
+                && (!gt || current.is_some_and(|current| updated > current))
+                && (!lt || current.map_or(true, |current| updated < current));
+            if allowed {
+                *expiry = Some(Expiry(Instant::now(), updated));
+                stream.write_all(b":1\r\n")?;
+            } else {
+                stream.write_all(b":0\r\n")?;
             }
         }
-        "set" => {
-            if msg.len() < 3 {
-                return Err(RESPError::InvalidArguments);
+        "persist" => {
+            let key = string_arg(msg, 1)?;
+            remove_if_expired(store, key);
+            let removed = store
+                .get_mut(key)
+                .and_then(|(_, expiry)| expiry.take())
+                .is_some();
+            stream.write_all(if removed { b":1\r\n" } else { b":0\r\n" })?;
+        }
+        "lpush" | "rpush" => {
+            let key = string_arg(msg, 1)?;
+            let mut elements = Vec::with_capacity(msg.len() - 2);
+            for i in 2..msg.len() {
+                elements.push(string_arg(msg, i)?.to_owned());
             }
-            let key = match msg.get(1).unwrap() {
-                RedisValue::String(key) => key,
-                _ => return Err(RESPError::InvalidArguments),
+            remove_if_expired(store, key);
+            let list = match list_entry(store, key) {
+                Ok(list) => list,
+                Err(reply) => {
+                    stream.write_all(reply)?;
+                    return Ok(());
+                }
             };
-            let value = match msg.get(2).unwrap() {
-                RedisValue::String(value) => value,
-                _ => return Err(RESPError::InvalidArguments),
+            // Each element is pushed in turn, so LPUSH leaves them reversed.
+            for element in elements {
+                if spec.name == "lpush" {
+                    list.push_front(element);
+                } else {
+                    list.push_back(element);
+                }
+            }
+            stream.write_all(format!(":{}\r\n", list.len()).as_bytes())?;
+            keyspace.pushed.notify_all();
+        }
+        "lpop" | "rpop" => {
+            let key = string_arg(msg, 1)?;
+            let count = match msg.get(2) {
+                None => None,
+                Some(_) => match parse_integer(string_arg(msg, 2)?) {
+                    Some(count) if count >= 0 => Some(count as usize),
+                    _ => {
+                        stream.write_all(b"-ERR value is out of range, must be positive\r\n")?;
+                        return Ok(());
+                    }
+                },
+            };
+            remove_if_expired(store, key);
+            let list = match store.get_mut(key) {
+                Some((StoredValue::List(list), _)) => list,
+                Some(_) => {
+                    stream.write_all(WRONGTYPE)?;
+                    return Ok(());
+                }
+                None => {
+                    stream.write_all(if count.is_some() { b"*-1\r\n" } else { NULL })?;
+                    return Ok(());
+                }
+            };
+            let popped: Vec<RedisValue> = (0..count.unwrap_or(1).min(list.len()))
+                .filter_map(|_| {
+                    if spec.name == "lpop" {
+                        list.pop_front()
+                    } else {
+                        list.pop_back()
+                    }
+                })
+                .map(RedisValue::String)
+                .collect();
+            // An empty list is never kept, so the key disappears with its last element.
+            if list.is_empty() {
+                store.remove(key);
+            }
+            match count {
+                Some(_) => stream.write_all(&RedisValue::Array(popped).encode())?,
+                None => stream.write_all(&popped[0].encode())?,
+            }
+        }
+        "blpop" => {
+            let keys = &msg[1..msg.len() - 1];
+            let timeout = from_utf8(string_arg(msg, msg.len() - 1)?).map(str::parse::<f64>);
+            let deadline = match timeout {
+                Ok(Ok(seconds)) if seconds < 0.0 => {
+                    stream.write_all(b"-ERR timeout is negative\r\n")?;
+                    return Ok(());
+                }
+                // Zero waits forever, and so does a deadline too far off to represent.
+                Ok(Ok(seconds)) => match Duration::try_from_secs_f64(seconds) {
+                    Ok(timeout) if timeout.is_zero() => None,
+                    Ok(timeout) => Instant::now().checked_add(timeout),
+                    Err(_) => {
+                        stream.write_all(b"-ERR timeout is out of range\r\n")?;
+                        return Ok(());
+                    }
+                },
+                _ => {
+                    stream.write_all(b"-ERR timeout is not a float or out of range\r\n")?;
+                    return Ok(());
+                }
+            };
+            loop {
+                for key in keys {
+                    let RedisValue::String(key) = key else {
+                        return Err(RESPError::InvalidArguments);
+                    };
+                    remove_if_expired(&mut guard, key);
+                    match guard.get_mut(key) {
+                        Some((StoredValue::List(list), _)) => {
+                            let element = list.pop_front().unwrap_or_default();
+                            if list.is_empty() {
+                                guard.remove(key);
+                            }
+                            let reply = RedisValue::Array(vec![
+                                RedisValue::String(key.to_owned()),
+                                RedisValue::String(element),
+                            ]);
+                            stream.write_all(&reply.encode())?;
+                            return Ok(());
+                        }
+                        Some(_) => {
+                            stream.write_all(WRONGTYPE)?;
+                            return Ok(());
+                        }
+                        None => {}
+                    }
+                }
+                // Waiting releases the lock so other clients can push meanwhile.
+                guard = match deadline {
+                    None => keyspace.pushed.wait(guard).unwrap(),
+                    Some(deadline) => {
+                        let now = Instant::now();
+                        if deadline <= now {
+                            stream.write_all(b"*-1\r\n")?;
+                            return Ok(());
+                        }
+                        keyspace
+                            .pushed
+                            .wait_timeout(guard, deadline - now)
+                            .unwrap()
+                            .0
+                    }
+                };
+            }
+        }
+        "llen" => {
+            let key = string_arg(msg, 1)?;
+            remove_if_expired(store, key);
+            match get_list(store, key) {
+                Ok(list) => {
+                    let len = list.map_or(0, VecDeque::len);
+                    stream.write_all(format!(":{}\r\n", len).as_bytes())?;
+                }
+                Err(reply) => stream.write_all(reply)?,
+            }
+        }
+        "lrange" => {
+            let key = string_arg(msg, 1)?;
+            let (Some(start), Some(stop)) = (
+                parse_integer(string_arg(msg, 2)?),
+                parse_integer(string_arg(msg, 3)?),
+            ) else {
+                stream.write_all(NOT_AN_INTEGER)?;
+                return Ok(());
+            };
+            remove_if_expired(store, key);
+            let list = match get_list(store, key) {
+                Ok(list) => list,
+                Err(reply) => {
+                    stream.write_all(reply)?;
+                    return Ok(());
+                }
+            };
+            let elements = list.map_or(vec![], |list| {
+                list_range(list.len(), start, stop)
+                    .map(|i| RedisValue::String(list[i].to_owned()))
+                    .collect()
+            });
+            stream.write_all(&RedisValue::Array(elements).encode())?;
+        }
+        "hset" => {
+            let key = string_arg(msg, 1)?;
+            let mut pairs = Vec::with_capacity((msg.len() - 2) / 2);
+            for i in (2..msg.len()).step_by(2) {
+                pairs.push((
+                    string_arg(msg, i)?.to_owned(),
+                    string_arg(msg, i + 1)?.to_owned(),
+                ));
+            }
+            remove_if_expired(store, key);
+            let hash = match hash_entry(store, key) {
+                Ok(hash) => hash,
+                Err(reply) => {
+                    stream.write_all(reply)?;
+                    return Ok(());
+                }
+            };
+            // Overwriting a field replaces its value but isn't counted.
+            let created = pairs
+                .into_iter()
+                .map(|(field, value)| hash.insert(field, value))
+                .filter(Option::is_none)
+                .count();
+            stream.write_all(format!(":{}\r\n", created).as_bytes())?;
+        }
+        "hget" => {
+            let key = string_arg(msg, 1)?;
+            let field = string_arg(msg, 2)?;
+            remove_if_expired(store, key);
+            match get_hash(store, key) {
+                Ok(hash) => match hash.and_then(|hash| hash.get(field)) {
+                    Some(value) => {
+                        stream.write_all(&RedisValue::String(value.to_owned()).encode())?
+                    }
+                    None => stream.write_all(NULL)?,
+                },
+                Err(reply) => stream.write_all(reply)?,
+            }
+        }
+        "hgetall" => {
+            let key = string_arg(msg, 1)?;
+            remove_if_expired(store, key);
+            match get_hash(store, key) {
+                Ok(hash) => {
+                    let pairs = hash
+                        .into_iter()
+                        .flatten()
+                        .flat_map(|(field, value)| [field, value])
+                        .map(|item| RedisValue::String(item.to_owned()))
+                        .collect();
+                    stream.write_all(&RedisValue::Array(pairs).encode())?;
+                }
+                Err(reply) => stream.write_all(reply)?,
+            }
+        }
+        "hdel" => {
+            let key = string_arg(msg, 1)?;
+            remove_if_expired(store, key);
+            let hash = match store.get_mut(key) {
+                Some((StoredValue::Hash(hash), _)) => hash,
+                Some(_) => {
+                    stream.write_all(WRONGTYPE)?;
+                    return Ok(());
+                }
+                None => {
+                    stream.write_all(b":0\r\n")?;
+                    return Ok(());
+                }
+            };
+            let mut removed = 0;
+            for i in 2..msg.len() {
+                if hash.remove(string_arg(msg, i)?).is_some() {
+                    removed += 1;
+                }
+            }
+            // An empty hash is never kept, so the key goes with its last field.
+            if hash.is_empty() {
+                store.remove(key);
+            }
+            stream.write_all(format!(":{}\r\n", removed).as_bytes())?;
+        }
+        "hexists" => {
+            let key = string_arg(msg, 1)?;
+            let field = string_arg(msg, 2)?;
+            remove_if_expired(store, key);
+            match get_hash(store, key) {
+                Ok(hash) => {
+                    let exists = hash.is_some_and(|hash| hash.contains_key(field));
+                    stream.write_all(if exists { b":1\r\n" } else { b":0\r\n" })?;
+                }
+                Err(reply) => stream.write_all(reply)?,
+            }
+        }
+        "hlen" => {
+            let key = string_arg(msg, 1)?;
+            remove_if_expired(store, key);
+            match get_hash(store, key) {
+                Ok(hash) => {
+                    let len = hash.map_or(0, HashMap::len);
+                    stream.write_all(format!(":{}\r\n", len).as_bytes())?;
+                }
+                Err(reply) => stream.write_all(reply)?,
+            }
+        }
+        "type" => {
+            let key = string_arg(msg, 1)?;
+            remove_if_expired(store, key);
+            let kind = match store.get(key) {
+                None => "none",
+                Some((StoredValue::Str(..), _)) => "string",
+                Some((StoredValue::List(_), _)) => "list",
+                Some((StoredValue::Hash(_), _)) => "hash",
+            };
+            stream.write_all(format!("+{}\r\n", kind).as_bytes())?;
+        }
+        "getrange" => {
+            let key = string_arg(msg, 1)?;
+            let (Some(start), Some(end)) = (
+                parse_integer(string_arg(msg, 2)?),
+                parse_integer(string_arg(msg, 3)?),
+            ) else {
+                stream.write_all(NOT_AN_INTEGER)?;
+                return Ok(());
+            };
+            remove_if_expired(store, key);
+            let value = match get_str(store, key) {
+                Ok(value) => value.map_or(&[][..], Vec::as_slice),
+                Err(reply) => {
+                    stream.write_all(reply)?;
+                    return Ok(());
+                }
+            };
+            let range = substring(value, start, end);
+            stream.write_all(format!("${}\r\n", range.len()).as_bytes())?;
+            stream.write_all(range)?;
+            stream.write_all(b"\r\n")?;
+        }
+        "setrange" => {
+            let key = string_arg(msg, 1)?;
+            let Some(offset) = parse_integer(string_arg(msg, 2)?) else {
+                stream.write_all(NOT_AN_INTEGER)?;
+                return Ok(());
             };
-            if msg.len() > 4 {
-                let flag = match msg.get(3).unwrap() {
-                    RedisValue::String(flag) => flag,
-                    _ => return Err(RESPError::InvalidArguments),
+            let value = string_arg(msg, 3)?;
+            if offset < 0 {
+                stream.write_all(b"-ERR offset is out of range\r\n")?;
+                return Ok(());
+            }
+            let offset = offset as usize;
+            remove_if_expired(store, key);
+            let current_len = match get_str(store, key) {
+                Ok(current) => current.map_or(0, Vec::len),
+                Err(reply) => {
+                    stream.write_all(reply)?;
+                    return Ok(());
+                }
+            };
+            // An empty value never creates or grows the key, it only reports the length.
+            if value.is_empty() {
+                stream.write_all(format!(":{}\r\n", current_len).as_bytes())?;
+                return Ok(());
+            }
+            if offset + value.len() > config.proto_max_bulk_len as usize {
+                stream.write_all(
+                    b"-ERR string exceeds maximum allowed size (proto-max-bulk-len)\r\n",
+                )?;
+                return Ok(());
+            }
+            // Modify in place so an existing TTL survives.
+            let current = match str_entry(store, key) {
+                Ok(current) => current,
+                Err(reply) => {
+                    stream.write_all(reply)?;
+                    return Ok(());
+                }
+            };
+            if current.len() < offset + value.len() {
+                current.resize(offset + value.len(), 0);
+            }
+            current[offset..offset + value.len()].copy_from_slice(value);
+            stream.write_all(format!(":{}\r\n", current.len()).as_bytes())?;
+        }
+        "memory" => {
+            match keyword(msg, 1)?.as_str() {
+                "usage" if msg.len() >= 3 => {
+                    let key = string_arg(msg, 2)?;
+                    // Every element is measured, so SAMPLES is validated but otherwise ignored.
+                    match &msg[3..] {
+                        [] => {}
+                        [RedisValue::String(flag), RedisValue::String(count)]
+                            if flag.eq_ignore_ascii_case(b"samples") =>
+                        {
+                            if parse_integer(count).is_none() {
+                                stream.write_all(NOT_AN_INTEGER)?;
+                                return Ok(());
+                            }
+                        }
+                        _ => {
+                            stream.write_all(SYNTAX_ERROR)?;
+                            return Ok(());
+                        }
+                    }
+                    remove_if_expired(store, key);
+                    match store.get(key) {
+                        Some(entry) => {
+                            let usage = memory_usage(key, entry);
+                            stream.write_all(format!(":{}\r\n", usage).as_bytes())?;
+                        }
+                        _ => stream.write_all(NULL)?,
+                    }
+                }
+                "doctor" if msg.len() == 2 => {
+                    let len = MEMORY_DOCTOR_REPORT.len();
+                    stream.write_all(
+                        format!("${}\r\n{}\r\n", len, MEMORY_DOCTOR_REPORT).as_bytes(),
+                    )?;
+                }
+                _ => return subcommand_error(stream, spec, &["usage", "doctor"], msg),
+            }
+        }
+        "object" => match keyword(msg, 1)?.as_str() {
+            "encoding" if msg.len() == 3 => {
+                let key = string_arg(msg, 2)?;
+                remove_if_expired(store, key);
+                match store.get(key) {
+                    Some((value, _)) => {
+                        let encoding = RedisValue::String(encoding(value).into());
+                        stream.write_all(&encoding.encode())?;
+                    }
+                    _ => stream.write_all(NULL)?,
+                }
+            }
+            _ => return subcommand_error(stream, spec, &["encoding"], msg),
+        },
+        "debug" => match keyword(msg, 1)?.as_str() {
+            "digest" if msg.len() == 2 => {
+                // XOR makes the result independent of the map's iteration order.
+                let digest = store
+                    .iter()
+                    .filter(|(_, (_, expiry))| !expiry.as_ref().is_some_and(Expiry::has_elapsed))
+                    .map(|(key, (value, expiry))| {
+                        let hash = fnv1a_prefixed(FNV_OFFSET_BASIS, key);
+                        let hash = fnv1a(hash, &digest_value(value).to_le_bytes());
+                        fnv1a(hash, &[expiry.is_some() as u8])
+                    })
+                    .fold(0, |acc, digest| acc ^ digest);
+                let digest = RedisValue::String(format!("{:016x}", digest).into());
+                stream.write_all(&digest.encode())?;
+            }
+            "digest-value" => {
+                let mut digests = Vec::with_capacity(msg.len() - 2);
+                for i in 2..msg.len() {
+                    let key = string_arg(msg, i)?;
+                    remove_if_expired(store, key);
+                    let digest = store.get(key).map_or(0, |(value, _)| digest_value(value));
+                    digests.push(RedisValue::String(format!("{:016x}", digest).into()));
+                }
+                stream.write_all(&RedisValue::Array(digests).encode())?;
+            }
+            _ => return subcommand_error(stream, spec, &["digest"], msg),
+        },
+        // There is no AUTH, so every connection is the default user, who may do anything.
+        "acl" => match keyword(msg, 1)?.as_str() {
+            "whoami" if msg.len() == 2 => {
+                stream.write_all(&RedisValue::String(DEFAULT_USER.into()).encode())?;
+            }
+            "list" if msg.len() == 2 => {
+                let mut rule = format!("user {} {}", DEFAULT_USER, DEFAULT_USER_RULES);
+                if !config.enable_admin_commands {
+                    rule.push_str(" -@admin");
+                }
+                stream.write_all(
+                    &RedisValue::Array(vec![RedisValue::String(rule.into())]).encode(),
+                )?;
+            }
+            _ => return subcommand_error(stream, spec, &["whoami", "list"], msg),
+        },
+        "client" => match keyword(msg, 1)?.as_str() {
+            "pause" if (3..=4).contains(&msg.len()) => {
+                let Some(millis) = parse_integer(string_arg(msg, 2)?).filter(|&ms| ms >= 0) else {
+                    stream.write_all(b"-ERR timeout is not an integer or out of range\r\n")?;
+                    return Ok(());
                 };
-                let opt = match msg.get(4).unwrap() {
-                    RedisValue::String(opt_as_str) => opt_as_str.parse::<u64>()?,
-                    _ => return Err(RESPError::InvalidArguments),
+                let writes_only = match msg.get(3) {
+                    None => false,
+                    Some(RedisValue::String(mode)) if mode.eq_ignore_ascii_case(b"all") => false,
+                    Some(RedisValue::String(mode)) if mode.eq_ignore_ascii_case(b"write") => true,
+                    _ => {
+                        stream.write_all(SYNTAX_ERROR)?;
+                        return Ok(());
+                    }
                 };
-                match flag.to_lowercase().as_str() {
-                    "px" => {
-                        store.insert(
-                            key.to_owned(),
-                            (
-                                value.to_owned(),
-                                Some(Expiry(Instant::now(), Duration::from_millis(opt))),
-                            ),
-                        );
-                        stream.write_all(OK)?;
-                    }
-                    _ => return Err(RESPError::InvalidArguments),
+                let until = Instant::now() + Duration::from_millis(millis as u64);
+                pause.pause(until, writes_only);
+                stream.write_all(OK)?;
+            }
+            "unpause" if msg.len() == 2 => {
+                pause.unpause();
+                stream.write_all(OK)?;
+            }
+            _ => return subcommand_error(stream, spec, &["pause", "unpause"], msg),
+        },
+        // Commands are listed under the names clients use, and disabled ones
+        // are left out, so every listing agrees with COUNT.
+        "command" => match msg.get(1) {
+            None => {
+                let info = config
+                    .commands()
+                    .map(|(name, spec)| info(name, spec))
+                    .collect();
+                stream.write_all(&RedisValue::Array(info).encode())?;
+            }
+            Some(RedisValue::String(subcommand)) if subcommand.eq_ignore_ascii_case(b"info") => {
+                let info = if msg.len() == 2 {
+                    config
+                        .commands()
+                        .map(|(name, spec)| info(name, spec))
+                        .collect()
+                } else {
+                    msg[2..]
+                        .iter()
+                        .map(|name| {
+                            named_command(config, name)
+                                .map_or(RedisValue::NullBulkString, |(name, spec)| info(name, spec))
+                        })
+                        .collect()
+                };
+                stream.write_all(&RedisValue::Array(info).encode())?;
+            }
+            Some(RedisValue::String(subcommand))
+                if subcommand.eq_ignore_ascii_case(b"count") && msg.len() == 2 =>
+            {
+                // A renamed command still counts once; one renamed to "" is disabled.
+                let count = config.commands().count();
+                stream.write_all(format!(":{}\r\n", count).as_bytes())?;
+            }
+            Some(RedisValue::String(subcommand)) if subcommand.eq_ignore_ascii_case(b"docs") => {
+                let specs: Vec<(&str, &CommandSpec)> = if msg.len() == 2 {
+                    config.commands().collect()
+                } else {
+                    msg[2..]
+                        .iter()
+                        .filter_map(|name| named_command(config, name))
+                        .collect()
+                };
+                let docs = specs
+                    .into_iter()
+                    .flat_map(|(name, spec)| [RedisValue::String(name.into()), docs(spec)])
+                    .collect();
+                stream.write_all(&RedisValue::Array(docs).encode())?;
+            }
+            _ => return subcommand_error(stream, spec, &["count"], msg),
+        },
+        "shutdown" => {
+            let save = match &msg[1..] {
+                [] => false,
+                [RedisValue::String(flag)] if flag.eq_ignore_ascii_case(b"nosave") => false,
+                [RedisValue::String(flag)] if flag.eq_ignore_ascii_case(b"save") => true,
+                _ => {
+                    stream.write_all(SYNTAX_ERROR)?;
+                    return Ok(());
                 }
+            };
+            if save {
+                // There is no persistence layer to write a dump with, so a
+                // forced save can only fail.
+                println!("Error: SHUTDOWN SAVE requested but persistence is unavailable");
+                stream.write_all(b"-ERR Errors trying to SHUTDOWN. Check logs.\r\n")?;
             } else {
-                store.insert(key.to_owned(), (value.to_owned(), None));
-                stream.write_all(OK)?;
+                // The connection flushes its replies and stops the server.
+                return Err(RESPError::Shutdown);
             }
         }
         _ => return Err(RESPError::InvalidCommand),
@@ -142,53 +1086,445 @@ pub fn execute(
     Ok(())
 }
 
-// Get a word from `buf` starting at `pos`
-fn word(buf: &[u8], pos: usize) -> Option<(usize, BufSplit)> {
-    if buf.len() <= pos {
+/// Drop `key` if its TTL has elapsed, so lookups that follow see it as absent.
+fn remove_if_expired(store: &mut KVStore, key: &[u8]) {
+    if store
+        .get(key)
+        .is_some_and(|(_, expiry)| expiry.as_ref().is_some_and(Expiry::has_elapsed))
+    {
+        store.remove(key);
+    }
+}
+
+/// Add `delta` to the integer stored at `key`, treating a missing key as 0.
+///
+/// The value is replaced but an existing TTL is kept. On failure the
+/// error reply to send is returned and the store is left untouched.
+fn increment(store: &mut KVStore, key: &[u8], delta: i64) -> Result<i64, &'static [u8]> {
+    remove_if_expired(store, key);
+    let current = match get_str(store, key)? {
+        Some(value) => parse_integer(value).ok_or(NOT_AN_INTEGER)?,
+        None => 0,
+    };
+    let updated = current.checked_add(delta).ok_or(OVERFLOW)?;
+    let value = StoredValue::string(updated.to_string().into_bytes());
+    match store.get_mut(key) {
+        Some((current, _)) => *current = value,
+        None => {
+            store.insert(key.to_owned(), (value, None));
+        }
+    }
+    Ok(updated)
+}
+
+/// Look up the string stored at `key`, or the WRONGTYPE reply if it holds
+/// another type.
+fn get_str<'a>(store: &'a KVStore, key: &[u8]) -> Result<Option<&'a Vec<u8>>, &'static [u8]> {
+    match store.get(key) {
+        Some((StoredValue::Str(value, _), _)) => Ok(Some(value)),
+        Some(_) => Err(WRONGTYPE),
+        None => Ok(None),
+    }
+}
+
+/// Borrow the string at `key` for modification in place, creating it empty
+/// if missing, or return the WRONGTYPE reply if it holds another type.
+///
+/// Redis turns a string it edits in place into `raw`, so this does too.
+fn str_entry<'a>(store: &'a mut KVStore, key: &[u8]) -> Result<&'a mut Vec<u8>, &'static [u8]> {
+    let (value, _) = store
+        .entry(key.to_owned())
+        .or_insert_with(|| (StoredValue::Str(Vec::new(), StrEncoding::Raw), None));
+    match value {
+        StoredValue::Str(value, encoding) => {
+            *encoding = StrEncoding::Raw;
+            Ok(value)
+        }
+        _ => Err(WRONGTYPE),
+    }
+}
+
+/// Look up the list stored at `key`, or the WRONGTYPE reply if it holds
+/// another type.
+fn get_list<'a>(
+    store: &'a KVStore,
+    key: &[u8],
+) -> Result<Option<&'a VecDeque<Vec<u8>>>, &'static [u8]> {
+    match store.get(key) {
+        Some((StoredValue::List(list), _)) => Ok(Some(list)),
+        Some(_) => Err(WRONGTYPE),
+        None => Ok(None),
+    }
+}
+
+/// Borrow the list at `key` for modification in place, creating it empty if
+/// missing, or return the WRONGTYPE reply if it holds another type.
+fn list_entry<'a>(
+    store: &'a mut KVStore,
+    key: &[u8],
+) -> Result<&'a mut VecDeque<Vec<u8>>, &'static [u8]> {
+    let (value, _) = store
+        .entry(key.to_owned())
+        .or_insert_with(|| (StoredValue::List(VecDeque::new()), None));
+    match value {
+        StoredValue::List(list) => Ok(list),
+        _ => Err(WRONGTYPE),
+    }
+}
+
+/// Look up the hash stored at `key`, or the WRONGTYPE reply if it holds
+/// another type.
+fn get_hash<'a>(store: &'a KVStore, key: &[u8]) -> Result<Option<&'a Fields>, &'static [u8]> {
+    match store.get(key) {
+        Some((StoredValue::Hash(hash), _)) => Ok(Some(hash)),
+        Some(_) => Err(WRONGTYPE),
+        None => Ok(None),
+    }
+}
+
+/// Borrow the hash at `key` for modification in place, creating it empty if
+/// missing, or return the WRONGTYPE reply if it holds another type.
+fn hash_entry<'a>(store: &'a mut KVStore, key: &[u8]) -> Result<&'a mut Fields, &'static [u8]> {
+    let (value, _) = store
+        .entry(key.to_owned())
+        .or_insert_with(|| (StoredValue::Hash(HashMap::new()), None));
+    match value {
+        StoredValue::Hash(hash) => Ok(hash),
+        _ => Err(WRONGTYPE),
+    }
+}
+
+/// Time left until the absolute Unix timestamp `millis`, or zero if it has passed.
+fn until_unix_millis(millis: i64) -> Duration {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    Duration::from_millis(millis as u64).saturating_sub(now)
+}
+
+/// Fetch argument `index` of a command as a string.
+fn string_arg(msg: &[RedisValue], index: usize) -> Result<&[u8], RESPError> {
+    match msg.get(index) {
+        Some(RedisValue::String(arg)) => Ok(arg),
+        _ => Err(RESPError::InvalidArguments),
+    }
+}
+
+/// Fetch argument `index` lowercased, for matching subcommand and option names.
+fn keyword(msg: &[RedisValue], index: usize) -> Result<String, RESPError> {
+    Ok(String::from_utf8_lossy(string_arg(msg, index)?).to_lowercase())
+}
+
+/// Reply to a subcommand of `spec` that can't run. One of `known` got the
+/// wrong number of arguments; anything else is an unknown subcommand.
+fn subcommand_error(
+    stream: &mut impl Write,
+    spec: &CommandSpec,
+    known: &[&str],
+    msg: &[RedisValue],
+) -> Result<(), RESPError> {
+    let subcommand = keyword(msg, 1)?;
+    let reply = if known.contains(&subcommand.as_str()) {
+        format!(
+            "-ERR wrong number of arguments for '{}|{}' command\r\n",
+            spec.name, subcommand
+        )
+    } else {
+        format!(
+            "-ERR unknown subcommand '{}'. Try {} HELP.\r\n",
+            String::from_utf8_lossy(string_arg(msg, 1)?),
+            spec.name.to_uppercase()
+        )
+    };
+    stream.write_all(reply.as_bytes())?;
+    Ok(())
+}
+
+/// Parse a command argument as an integer the way Redis does: an optional
+/// sign followed by digits, with no whitespace and no fractional part.
+fn parse_integer(arg: &[u8]) -> Option<i64> {
+    let digits = match arg {
+        [b'+' | b'-', digits @ ..] => digits,
+        digits => digits,
+    };
+    if digits.is_empty() || !digits.iter().all(u8::is_ascii_digit) {
         return None;
     }
-    let mut end = pos;
-    while buf[end] != b'\r' {
-        end += 1;
-        if buf.len() < end + 1 {
-            return None;
+    from_utf8(arg).ok()?.parse().ok()
+}
+
+/// Match `subject` against a glob `pattern` byte by byte, as Redis does.
+///
+/// Supports `*`, `?`, `[...]` classes with ranges and `^` negation, and `\`
+/// escapes. Nothing here assumes UTF-8, so any byte can be matched.
+fn glob_match(pattern: &[u8], subject: &[u8]) -> bool {
+    let (mut p, mut s) = (0, 0);
+    // Pattern position just after the last `*` seen, and the subject position
+    // it has been stretched to. Only the last star ever needs to backtrack, so
+    // matching takes O(pattern * subject) time.
+    let mut star = None;
+    loop {
+        if pattern.get(p) == Some(&b'*') {
+            p += 1;
+            star = Some((p, s));
+            continue;
+        }
+        let Some(&b) = subject.get(s) else {
+            return p == pattern.len();
+        };
+        if let Some(len) = match_token(&pattern[p..], b) {
+            p += len;
+            s += 1;
+            continue;
+        }
+        // Let the last star absorb one more byte and retry from there.
+        match star {
+            Some((after_star, stretched)) => {
+                star = Some((after_star, stretched + 1));
+                p = after_star;
+                s = stretched + 1;
+            }
+            None => return false,
+        }
+    }
+}
+
+/// Match the single-byte token at the start of `pattern`, which is not `*`,
+/// against `b`. Returns how many pattern bytes the token spans if it matches.
+fn match_token(pattern: &[u8], b: u8) -> Option<usize> {
+    match pattern {
+        [] => None,
+        [b'?', ..] => Some(1),
+        [b'[', class @ ..] => {
+            let (matched, rest) = match_class(class, b);
+            matched.then(|| pattern.len() - rest.len())
+        }
+        [b'\\', literal, ..] => (*literal == b).then_some(2),
+        [literal, ..] => (*literal == b).then_some(1),
+    }
+}
+
+/// Test `b` against the body of a `[...]` class, returning the result and the
+/// pattern left after the closing bracket.
+fn match_class(mut class: &[u8], b: u8) -> (bool, &[u8]) {
+    let negate = class.first() == Some(&b'^');
+    if negate {
+        class = &class[1..];
+    }
+    let mut matched = false;
+    loop {
+        match class {
+            // An unterminated class runs to the end of the pattern.
+            [] => break,
+            [b']', rest @ ..] => {
+                class = rest;
+                break;
+            }
+            [b'\\', literal, rest @ ..] => {
+                matched |= *literal == b;
+                class = rest;
+            }
+            [low, b'-', high, rest @ ..] if *high != b']' => {
+                matched |= (*low.min(high)..=*low.max(high)).contains(&b);
+                class = rest;
+            }
+            [literal, rest @ ..] => {
+                matched |= *literal == b;
+                class = rest;
+            }
         }
     }
-    Some((end + 2, BufSplit(pos, end)))
+    (matched != negate, class)
+}
+
+/// Slice `value` by the inclusive, possibly negative, offsets GETRANGE takes.
+fn substring(value: &[u8], start: i64, end: i64) -> &[u8] {
+    let len = value.len() as i64;
+    if start < 0 && end < 0 && start > end {
+        return &[];
+    }
+    let start = if start < 0 {
+        (len + start).max(0)
+    } else {
+        start
+    };
+    let end = if end < 0 {
+        (len + end).max(0)
+    } else {
+        end.min(len - 1)
+    };
+    if len == 0 || start > end {
+        return &[];
+    }
+    &value[start as usize..=end as usize]
+}
+
+/// Resolve the inclusive, possibly negative, LRANGE bounds against a list of
+/// `len` elements, clamping them to the list.
+fn list_range(len: usize, start: i64, stop: i64) -> std::ops::Range<usize> {
+    let len = len as i64;
+    let start = if start < 0 { len + start } else { start }.max(0);
+    let stop = if stop < 0 { len + stop } else { stop }.min(len - 1);
+    if start > stop {
+        return 0..0;
+    }
+    start as usize..stop as usize + 1
+}
+
+/// Look up the command a client names in COMMAND INFO or DOCS, along with the
+/// name it is invoked by.
+fn named_command<'a>(
+    config: &'a Config,
+    name: &RedisValue,
+) -> Option<(&'a str, &'static CommandSpec)> {
+    match name {
+        RedisValue::String(name) => config
+            .resolve(name)
+            .and_then(|spec| Some((config.exposed_name(spec)?, spec))),
+        _ => None,
+    }
+}
+
+/// Build the `COMMAND INFO` entry for a command clients invoke as `name`.
+fn info(name: &str, spec: &CommandSpec) -> RedisValue {
+    let (first_key, last_key, step) = spec.keys;
+    RedisValue::Array(vec![
+        RedisValue::String(name.into()),
+        RedisValue::Int(spec.arity),
+        RedisValue::Array(
+            spec.flags
+                .iter()
+                .map(|flag| RedisValue::String((*flag).into()))
+                .collect(),
+        ),
+        RedisValue::Int(first_key),
+        RedisValue::Int(last_key),
+        RedisValue::Int(step),
+    ])
+}
+
+/// Build the `COMMAND DOCS` entry for a single command as a flattened map.
+fn docs(spec: &CommandSpec) -> RedisValue {
+    RedisValue::Array(vec![
+        RedisValue::String("summary".into()),
+        RedisValue::String(spec.summary.into()),
+        RedisValue::String("since".into()),
+        RedisValue::String(spec.since.into()),
+        RedisValue::String("group".into()),
+        RedisValue::String(spec.group.into()),
+        RedisValue::String("arity".into()),
+        RedisValue::Int(spec.arity),
+    ])
+}
+
+/// Report how Redis would encode a value.
+fn encoding(value: &StoredValue) -> &'static str {
+    match value {
+        StoredValue::Str(_, StrEncoding::Int) => "int",
+        StoredValue::Str(_, StrEncoding::Embstr) => "embstr",
+        StoredValue::Str(_, StrEncoding::Raw) => "raw",
+        StoredValue::List(_) => "quicklist",
+        StoredValue::Hash(_) => "hashtable",
+    }
+}
+
+/// Fold `bytes` into a 64-bit FNV-1a hash. Unlike `DefaultHasher` its output
+/// is fixed, so digests can be compared across builds and Rust releases.
+fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(FNV_PRIME)
+    })
+}
+
+/// Fold `bytes` into `hash` preceded by their length, so that adjacent
+/// elements can't run into each other.
+fn fnv1a_prefixed(hash: u64, bytes: &[u8]) -> u64 {
+    fnv1a(fnv1a(hash, &(bytes.len() as u64).to_le_bytes()), bytes)
+}
+
+/// Hash a stored value for DEBUG DIGEST-VALUE.
+fn digest_value(value: &StoredValue) -> u64 {
+    match value {
+        StoredValue::Str(value, _) => fnv1a(FNV_OFFSET_BASIS, value),
+        StoredValue::List(items) => items
+            .iter()
+            .fold(FNV_OFFSET_BASIS, |hash, item| fnv1a_prefixed(hash, item)),
+        // Fields have no order, so their digests are combined with XOR.
+        StoredValue::Hash(fields) => fields
+            .iter()
+            .map(|(field, value)| fnv1a_prefixed(fnv1a_prefixed(FNV_OFFSET_BASIS, field), value))
+            .fold(0, |acc, digest| acc ^ digest),
+    }
+}
+
+/// Estimate the bytes a single entry occupies in the store.
+///
+/// Counts the inline size of the map slot plus the heap bytes owned by the key
+/// and value. Allocator padding and hash table control bytes are not included.
+fn memory_usage(key: &[u8], entry: &Entry) -> usize {
+    let bytes = std::mem::size_of::<Vec<u8>>();
+    let value = match &entry.0 {
+        StoredValue::Str(value, _) => value.len(),
+        StoredValue::List(items) => items.iter().map(|item| bytes + item.len()).sum(),
+        StoredValue::Hash(fields) => fields
+            .iter()
+            .map(|(field, value)| 2 * bytes + field.len() + value.len())
+            .sum(),
+    };
+    std::mem::size_of::<(Vec<u8>, Entry)>() + key.len() + value
+}
+
+// Get a word from `buf` starting at `pos`. `None` means the terminating
+// "\r\n" hasn't fully arrived yet.
+fn word(buf: &[u8], pos: usize) -> Result<Option<(usize, BufSplit)>, RESPError> {
+    let Some(end) = buf
+        .get(pos..)
+        .and_then(|rest| rest.iter().position(|&b| b == b'\r'))
+    else {
+        return Ok(None);
+    };
+    let end = pos + end;
+    match buf.get(end + 1) {
+        None => Ok(None),
+        Some(b'\n') => Ok(Some((end + 2, BufSplit(pos, end)))),
+        Some(_) => Err(RESPError::UnterminatedLine),
+    }
 }
 
 fn simple_string(buf: &[u8], pos: usize) -> RedisResult {
-    match word(buf, pos) {
+    match word(buf, pos)? {
         Some((pos, word)) => {
-            let str = from_utf8(word.as_slice(buf)).unwrap();
-            let res = RedisValue::String(str.to_string());
+            let res = RedisValue::String(word.as_slice(buf).to_vec());
             Ok(Some((pos, res)))
         }
         None => Ok(None),
     }
 }
 
-fn bulk_string(buf: &[u8], pos: usize) -> RedisResult {
+fn bulk_string(buf: &[u8], pos: usize, max_len: i64) -> RedisResult {
     match int(buf, pos)? {
         Some((pos, -1)) => Ok(Some((pos, RedisValue::NullBulkString))),
-        Some((pos, size)) if size >= 0 => {
-            let total_size = pos + size as usize;
-            if buf.len() < total_size + 2 {
+        Some((pos, size)) if (0..=max_len).contains(&size) => {
+            // The declared size is authoritative, so the payload may contain CR or LF.
+            let end = pos + size as usize;
+            if buf.len() < end + 2 {
                 Ok(None)
+            } else if &buf[end..end + 2] != b"\r\n" {
+                Err(RESPError::UnterminatedBulkString)
             } else {
-                simple_string(buf, pos)
+                Ok(Some((end + 2, RedisValue::String(buf[pos..end].to_vec()))))
             }
         }
-        Some((_pos, bad_size)) => Err(RESPError::BadBulkStringSize(bad_size)),
+        Some(_) => Err(RESPError::BadBulkStringSize),
         None => Ok(None),
     }
 }
 
 fn error(buf: &[u8], pos: usize) -> RedisResult {
-    match word(buf, pos) {
+    match word(buf, pos)? {
         Some((pos, word)) => {
-            let str = from_utf8(word.as_slice(buf)).unwrap();
-            let res = RedisValue::Error(str.to_string());
+            let str = String::from_utf8_lossy(word.as_slice(buf));
+            let res = RedisValue::Error(str.into_owned());
             Ok(Some((pos, res)))
         }
         None => Ok(None),
@@ -196,7 +1532,7 @@ fn error(buf: &[u8], pos: usize) -> RedisResult {
 }
 
 fn int(buf: &[u8], pos: usize) -> Result<Option<(usize, i64)>, RESPError> {
-    match word(buf, pos) {
+    match word(buf, pos)? {
         Some((pos, word)) => {
             let s =
                 std::str::from_utf8(word.as_slice(buf)).map_err(|_| RESPError::IntParseFailure)?;
@@ -214,24 +1550,27 @@ fn redis_int(buf: &[u8], pos: usize) -> RedisResult {
     }
 }
 
-fn array(buf: &[u8], pos: usize) -> RedisResult {
+fn array(buf: &[u8], pos: usize, max_bulk_len: i64) -> RedisResult {
     match int(buf, pos)? {
         Some((pos, -1)) => Ok(Some((pos, RedisValue::NullArray))),
-        Some((pos, arr_size)) if arr_size >= 0 => {
-            let mut res: Vec<RedisValue> = Vec::with_capacity(arr_size as usize);
+        Some((pos, arr_size)) if (0..=MAX_MULTIBULK_LEN).contains(&arr_size) => {
+            // Every element takes at least one buffered byte, so reserve no more
+            // than that however many the client claims.
+            let capacity = (arr_size as usize).min(buf.len() - pos);
+            let mut res: Vec<RedisValue> = Vec::with_capacity(capacity);
             let mut curr_pos = pos;
             for _ in 0..arr_size {
-                match parse(buf, curr_pos)? {
+                match parse(buf, curr_pos, max_bulk_len)? {
                     Some((pos, val)) => {
                         res.push(val);
                         curr_pos = pos;
                     }
-                    None => return Err(RESPError::UnexpectedEnd),
+                    None => return Ok(None),
                 }
             }
-            Ok(Some((pos, RedisValue::Array(res))))
+            Ok(Some((curr_pos, RedisValue::Array(res))))
         }
-        Some((_pos, bad_size)) => Err(RESPError::BadArraySize(bad_size)),
+        Some(_) => Err(RESPError::BadArraySize),
         None => Ok(None),
     }
 }
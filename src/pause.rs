@@ -0,0 +1,41 @@
+use std::sync::{Condvar, Mutex};
+use std::time::Instant;
+
+use crate::commands::CommandSpec;
+
+/// The `CLIENT PAUSE` window shared by every connection.
+#[derive(Default)]
+pub struct ClientPause {
+    /// When the pause ends and whether it only holds back write commands.
+    window: Mutex<Option<(Instant, bool)>>,
+    lifted: Condvar,
+}
+
+impl ClientPause {
+    pub fn pause(&self, until: Instant, writes_only: bool) {
+        *self.window.lock().unwrap() = Some((until, writes_only));
+        // Waiters re-check, in case the new window no longer covers them.
+        self.lifted.notify_all();
+    }
+
+    pub fn unpause(&self) {
+        *self.window.lock().unwrap() = None;
+        self.lifted.notify_all();
+    }
+
+    /// Block until `spec` may run, returning at once if no pause applies to it.
+    pub fn wait(&self, spec: &CommandSpec) {
+        // CLIENT stays available so a paused server can still be unpaused.
+        if spec.name == "client" {
+            return;
+        }
+        let mut window = self.window.lock().unwrap();
+        while let Some((until, writes_only)) = *window {
+            let now = Instant::now();
+            if until <= now || (writes_only && !spec.flags.contains(&"write")) {
+                return;
+            }
+            window = self.lifted.wait_timeout(window, until - now).unwrap().0;
+        }
+    }
+}
@@ -0,0 +1,917 @@
+//! End-to-end tests. Commands go through `handle_connection` either over an
+//! in-memory stream or over TCP to a server booted in-process on a free port.
+
+use std::collections::HashMap;
+use std::io::{Cursor, Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::config::Config;
+use crate::parser::{parse, Keyspace};
+use crate::pause::ClientPause;
+use crate::{handle_connection, listen, serve, Listener};
+
+/// Encode `args` as a RESP array of bulk strings, the way clients send commands.
+fn command<A: AsRef<[u8]>>(args: &[A]) -> Vec<u8> {
+    let mut out = format!("*{}\r\n", args.len()).into_bytes();
+    for arg in args {
+        let arg = arg.as_ref();
+        out.extend(format!("${}\r\n", arg.len()).into_bytes());
+        out.extend(arg);
+        out.extend(b"\r\n");
+    }
+    out
+}
+
+/// A client connection held in memory: reads drain `input`, writes collect in `output`.
+struct Pipe {
+    input: Cursor<Vec<u8>>,
+    output: Vec<u8>,
+    /// Number of `write` calls the server made.
+    writes: usize,
+}
+
+impl Pipe {
+    fn new(input: &[u8]) -> Pipe {
+        Pipe {
+            input: Cursor::new(input.to_vec()),
+            output: Vec::new(),
+            writes: 0,
+        }
+    }
+}
+
+impl Read for Pipe {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.input.read(buf)
+    }
+}
+
+impl Write for Pipe {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.writes += 1;
+        self.output.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Server state shared by in-memory connections, without any sockets.
+struct Server {
+    store: Keyspace,
+    pause: ClientPause,
+    config: Config,
+}
+
+impl Server {
+    fn new() -> Server {
+        Server::with_config(Config::default())
+    }
+
+    fn with_config(config: Config) -> Server {
+        Server {
+            store: Keyspace::default(),
+            pause: ClientPause::default(),
+            config,
+        }
+    }
+
+    /// Open a connection, send `input`, hang up, and return what the server wrote.
+    fn session(&self, input: &[u8]) -> Pipe {
+        let mut pipe = Pipe::new(input);
+        handle_connection(&mut pipe, &self.store, &self.pause, &self.config);
+        pipe
+    }
+
+    /// Run a single command on its own connection and return the raw reply.
+    fn call<A: AsRef<[u8]>>(&self, args: &[A]) -> Vec<u8> {
+        self.session(&command(args)).output
+    }
+}
+
+/// Config for a `TestServer`, on a port the OS picks.
+fn ephemeral() -> Config {
+    Config {
+        port: 0,
+        ..Config::default()
+    }
+}
+
+/// Run `serve` on a thread with fresh state, the way `main` does, until
+/// `running` is cleared.
+fn spawn_serve<L: Listener + Send + 'static>(
+    listener: L,
+    config: Config,
+    running: &Arc<AtomicBool>,
+) -> JoinHandle<()> {
+    let running = Arc::clone(running);
+    thread::spawn(move || {
+        let store = Arc::new(Keyspace::default());
+        let pause = Arc::new(ClientPause::default());
+        serve(listener, &store, &pause, &Arc::new(config), &running);
+    })
+}
+
+/// A server listening on a local port. Dropping it stops the accept loop and
+/// waits for every connection thread, so clients must be dropped first.
+struct TestServer {
+    addr: SocketAddr,
+    running: Arc<AtomicBool>,
+    acceptor: Option<JoinHandle<()>>,
+}
+
+impl TestServer {
+    fn start(config: Config) -> TestServer {
+        let listener = listen(&config).unwrap();
+        let addr = listener.local_addr().unwrap();
+        let running = Arc::new(AtomicBool::new(true));
+        let acceptor = spawn_serve(listener, config, &running);
+        TestServer {
+            addr,
+            running,
+            acceptor: Some(acceptor),
+        }
+    }
+
+    fn connect(&self) -> Client {
+        let stream = TcpStream::connect(self.addr).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        Client {
+            stream,
+            buf: Vec::new(),
+        }
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::SeqCst);
+        // The accept loop only checks the flag when a connection arrives.
+        let _ = TcpStream::connect(self.addr);
+        if let Some(acceptor) = self.acceptor.take() {
+            acceptor.join().unwrap();
+        }
+    }
+}
+
+/// A TCP connection to a `TestServer`.
+struct Client {
+    stream: TcpStream,
+    buf: Vec<u8>,
+}
+
+impl Client {
+    fn send(&mut self, bytes: &[u8]) {
+        self.stream.write_all(bytes).unwrap();
+    }
+
+    /// Read one complete reply, returning its raw bytes.
+    fn reply(&mut self) -> Vec<u8> {
+        loop {
+            if let Ok(Some((len, _))) = parse(&self.buf, 0, i64::MAX) {
+                return self.buf.drain(..len).collect();
+            }
+            let mut chunk = [0; 512];
+            let n = self.stream.read(&mut chunk).unwrap();
+            assert!(n > 0, "connection closed before a full reply arrived");
+            self.buf.extend_from_slice(&chunk[..n]);
+        }
+    }
+
+    fn call<A: AsRef<[u8]>>(&mut self, args: &[A]) -> Vec<u8> {
+        self.send(&command(args));
+        self.reply()
+    }
+
+    /// Wait for the server to hang up, returning false if it sends data instead.
+    fn is_closed(&mut self) -> bool {
+        let mut chunk = [0; 512];
+        matches!(self.stream.read(&mut chunk), Ok(0))
+    }
+}
+
+#[test]
+fn harness_serves_set_and_get_over_tcp() {
+    let server = TestServer::start(ephemeral());
+    let mut client = server.connect();
+    assert_eq!(client.call(&["SET", "k", "v"]), b"+OK\r\n");
+    assert_eq!(client.call(&["GET", "k"]), b"$1\r\nv\r\n");
+    drop(client);
+    drop(server);
+}
+
+#[test]
+fn harness_runs_commands_in_memory() {
+    let server = Server::new();
+    assert_eq!(server.call(&["PING"]), b"+PONG\r\n");
+    let mut input = command(&["ECHO", "a"]);
+    input.extend(command(&["ECHO", "b"]));
+    assert_eq!(server.session(&input).output, b"$1\r\na\r\n$1\r\nb\r\n");
+}
+
+#[test]
+fn set_then_get_through_an_in_memory_stream() {
+    let server = Server::new();
+    let mut input = command(&["SET", "greeting", "hello"]);
+    input.extend(command(&["GET", "greeting"]));
+    assert_eq!(server.session(&input).output, b"+OK\r\n$5\r\nhello\r\n");
+}
+
+#[test]
+fn keys_matches_a_non_utf8_byte_with_a_wildcard() {
+    let server = Server::new();
+    let key: &[u8] = b"user:\xff";
+    assert_eq!(server.call(&[&b"SET"[..], key, b"v"]), b"+OK\r\n");
+    assert_eq!(
+        server.call(&["KEYS", "user:?"]),
+        b"*1\r\n$6\r\nuser:\xff\r\n"
+    );
+    assert_eq!(server.call(&[&b"KEYS"[..], b"\xff"]), b"*0\r\n");
+    assert_eq!(server.call(&[&b"GET"[..], key]), b"$1\r\nv\r\n");
+}
+
+#[test]
+fn bulk_strings_are_read_by_their_declared_length() {
+    let server = Server::new();
+    let mut input = command(&["SET", "k", "a\rb"]);
+    input.extend(command(&["PING"]));
+    input.extend(command(&["GET", "k"]));
+    assert_eq!(
+        server.session(&input).output,
+        b"+OK\r\n+PONG\r\n$3\r\na\rb\r\n"
+    );
+}
+
+#[test]
+fn bulk_string_without_trailing_crlf_is_a_protocol_error() {
+    let server = Server::new();
+    let mut input = b"*2\r\n$4\r\nECHO\r\n$1\r\nab\r\n".to_vec();
+    input.extend(command(&["PING"]));
+    let output = server.session(&input).output;
+    assert!(output.starts_with(b"-ERR Protocol error"));
+    assert!(!output.ends_with(b"+PONG\r\n"));
+}
+
+#[test]
+fn pipelined_replies_are_coalesced() {
+    let server = Server::new();
+    let mut input = Vec::new();
+    for i in 0..1000 {
+        input.extend(command(&["SET", &format!("key:{}", i), "v"]));
+    }
+    let pipe = server.session(&input);
+    assert_eq!(pipe.output, b"+OK\r\n".repeat(1000));
+    assert!(pipe.writes < 100, "{} writes for 1000 replies", pipe.writes);
+}
+
+#[test]
+fn lone_command_is_answered_without_more_input() {
+    let server = TestServer::start(ephemeral());
+    let mut client = server.connect();
+    let started = Instant::now();
+    assert_eq!(client.call(&["PING"]), b"+PONG\r\n");
+    assert!(started.elapsed() < Duration::from_secs(1));
+}
+
+#[test]
+fn bulk_length_above_the_configured_limit_is_rejected() {
+    let server = Server::with_config(Config {
+        proto_max_bulk_len: 8,
+        ..Config::default()
+    });
+    assert_eq!(server.call(&["ECHO", "12345678"]), b"$8\r\n12345678\r\n");
+    let mut input = command(&["ECHO", "123456789"]);
+    input.extend(command(&["PING"]));
+    assert_eq!(
+        server.session(&input).output,
+        b"-ERR Protocol error: invalid bulk length\r\n"
+    );
+}
+
+#[test]
+fn multibulk_length_above_the_limit_is_rejected() {
+    let server = Server::new();
+    for header in [&b"*4000000000\r\n"[..], b"*1048577\r\n"] {
+        assert_eq!(
+            server.session(header).output,
+            b"-ERR Protocol error: invalid multibulk length\r\n"
+        );
+    }
+    // A count within the limit just waits for its elements to arrive.
+    assert_eq!(server.session(b"*1048576\r\n").output, b"");
+}
+
+#[test]
+fn setrange_overwrites_bytes_even_inside_a_utf8_character() {
+    let server = Server::new();
+    server.call(&["SET", "u", "é"]);
+    assert_eq!(server.call(&["SETRANGE", "u", "1", "a"]), b":2\r\n");
+    assert_eq!(server.call(&["GET", "u"]), b"$2\r\n\xc3a\r\n");
+}
+
+#[test]
+fn setrange_with_empty_value_on_missing_key_creates_nothing() {
+    let server = Server::new();
+    assert_eq!(server.call(&["SETRANGE", "k", "5", ""]), b":0\r\n");
+    assert_eq!(server.call(&["EXISTS", "k"]), b":0\r\n");
+    assert_eq!(server.call(&["DBSIZE"]), b":0\r\n");
+}
+
+#[test]
+fn getrange_on_missing_key_is_empty() {
+    let server = Server::new();
+    assert_eq!(server.call(&["GETRANGE", "k", "0", "-1"]), b"$0\r\n\r\n");
+    assert_eq!(server.call(&["DBSIZE"]), b":0\r\n");
+}
+
+#[test]
+fn append_of_empty_string_creates_the_key() {
+    let server = Server::new();
+    assert_eq!(server.call(&["APPEND", "k", ""]), b":0\r\n");
+    assert_eq!(server.call(&["DBSIZE"]), b":1\r\n");
+    assert_eq!(server.call(&["STRLEN", "k"]), b":0\r\n");
+    assert_eq!(server.call(&["GET", "k"]), b"$0\r\n\r\n");
+}
+
+#[test]
+fn object_encoding_follows_how_the_string_was_written() {
+    let server = Server::new();
+    server.call(&["SET", "short", "0123456789"]);
+    assert_eq!(
+        server.call(&["OBJECT", "ENCODING", "short"]),
+        b"$6\r\nembstr\r\n"
+    );
+    server.call(&["SET", "long", &"x".repeat(100)]);
+    assert_eq!(
+        server.call(&["OBJECT", "ENCODING", "long"]),
+        b"$3\r\nraw\r\n"
+    );
+    server.call(&["SET", "n", "10"]);
+    assert_eq!(server.call(&["OBJECT", "ENCODING", "n"]), b"$3\r\nint\r\n");
+    server.call(&["APPEND", "n", "5"]);
+    assert_eq!(server.call(&["OBJECT", "ENCODING", "n"]), b"$3\r\nraw\r\n");
+}
+
+#[test]
+fn append_to_an_int_keeps_length_value_and_encoding_consistent() {
+    let server = Server::new();
+    server.call(&["SET", "n", "12345"]);
+    assert_eq!(server.call(&["APPEND", "n", "678"]), b":8\r\n");
+    assert_eq!(server.call(&["STRLEN", "n"]), b":8\r\n");
+    assert_eq!(server.call(&["GET", "n"]), b"$8\r\n12345678\r\n");
+    assert_eq!(
+        server.call(&["GETRANGE", "n", "0", "-1"]),
+        b"$8\r\n12345678\r\n"
+    );
+    assert_eq!(server.call(&["OBJECT", "ENCODING", "n"]), b"$3\r\nraw\r\n");
+}
+
+#[test]
+fn renamed_command_answers_only_to_its_new_name() {
+    let mut renamed_commands = HashMap::new();
+    renamed_commands.insert("debug".to_string(), "secret-debug".to_string());
+    renamed_commands.insert("dbsize".to_string(), String::new());
+    let server = Server::with_config(Config {
+        renamed_commands,
+        ..Config::default()
+    });
+    let mut input = command(&["DEBUG", "DIGEST"]);
+    input.extend(command(&["DBSIZE"]));
+    input.extend(command(&["PING"]));
+    assert_eq!(
+        server.session(&input).output,
+        b"-ERR unknown command 'DEBUG'\r\n-ERR unknown command 'DBSIZE'\r\n+PONG\r\n"
+    );
+    assert_eq!(
+        server.call(&["SECRET-DEBUG", "DIGEST"]),
+        b"$16\r\n0000000000000000\r\n"
+    );
+}
+
+#[test]
+fn bad_subcommands_get_an_error_reply() {
+    let server = Server::new();
+    let mut input = command(&["CLIENT", "Bogus"]);
+    input.extend(command(&["MEMORY", "USAGE"]));
+    input.extend(command(&["OBJECT", "ENCODING"]));
+    input.extend(command(&["ACL", "WHOAMI", "extra"]));
+    input.extend(command(&["PING"]));
+    assert_eq!(
+        server.session(&input).output,
+        b"-ERR unknown subcommand 'Bogus'. Try CLIENT HELP.\r\n\
+          -ERR wrong number of arguments for 'memory|usage' command\r\n\
+          -ERR wrong number of arguments for 'object|encoding' command\r\n\
+          -ERR wrong number of arguments for 'acl|whoami' command\r\n\
+          +PONG\r\n"
+    );
+}
+
+#[test]
+fn client_pause_write_delays_writes_but_not_reads() {
+    let server = TestServer::start(ephemeral());
+    let mut admin = server.connect();
+    let mut writer = server.connect();
+    let mut reader = server.connect();
+    assert_eq!(admin.call(&["SET", "k", "old"]), b"+OK\r\n");
+
+    let pause = Duration::from_millis(300);
+    let started = Instant::now();
+    assert_eq!(admin.call(&["CLIENT", "PAUSE", "300", "WRITE"]), b"+OK\r\n");
+    let set = thread::spawn(move || {
+        let reply = writer.call(&["SET", "k", "new"]);
+        (reply, started.elapsed())
+    });
+    // Give the SET time to reach the server and start waiting.
+    thread::sleep(Duration::from_millis(50));
+    assert_eq!(reader.call(&["GET", "k"]), b"$3\r\nold\r\n");
+    assert!(started.elapsed() < pause, "GET waited for the pause");
+
+    let (reply, elapsed) = set.join().unwrap();
+    assert_eq!(reply, b"+OK\r\n");
+    assert!(elapsed >= pause, "SET answered after {:?}", elapsed);
+    assert_eq!(reader.call(&["GET", "k"]), b"$3\r\nnew\r\n");
+}
+
+#[test]
+fn arity_rules_reject_unpaired_and_excess_arguments() {
+    let server = Server::new();
+    let mut input = command(&["MSET", "a", "1", "b"]);
+    input.extend(command(&["HSET", "h", "f1", "v1", "f2"]));
+    input.extend(command(&["LPOP", "l", "1", "2"]));
+    input.extend(command(&["DBSIZE"]));
+    assert_eq!(
+        server.session(&input).output,
+        b"-ERR wrong number of arguments for 'mset' command\r\n\
+          -ERR wrong number of arguments for 'hset' command\r\n\
+          -ERR wrong number of arguments for 'lpop' command\r\n\
+          :0\r\n"
+    );
+    assert_eq!(server.call(&["MSET", "a", "1", "b", "2"]), b"+OK\r\n");
+    assert_eq!(server.call(&["GET", "b"]), b"$1\r\n2\r\n");
+}
+
+#[test]
+fn incrby_accepts_only_plain_integers() {
+    let server = Server::new();
+    for delta in [" 5", "5 ", "5.0", ""] {
+        assert_eq!(
+            server.call(&["INCRBY", "n", delta]),
+            b"-ERR value is not an integer or out of range\r\n",
+            "INCRBY by {:?}",
+            delta
+        );
+    }
+    assert_eq!(server.call(&["INCRBY", "n", "+5"]), b":5\r\n");
+    assert_eq!(server.call(&["INCRBY", "n", "-7"]), b":-2\r\n");
+}
+
+#[test]
+fn digests_are_fixed_and_ignore_hash_insertion_order() {
+    let server = Server::new();
+    server.call(&["SET", "s", "a"]);
+    assert_eq!(
+        server.call(&["DEBUG", "DIGEST-VALUE", "s"]),
+        b"*1\r\n$16\r\naf63dc4c8601ec8c\r\n"
+    );
+
+    server.call(&["HSET", "h1", "a", "1", "b", "2", "c", "3"]);
+    server.call(&["HSET", "h2", "c", "3", "a", "1", "b", "2"]);
+    let digests = server.call(&["DEBUG", "DIGEST-VALUE", "h1", "h2"]);
+    let (first, second) = digests[4..].split_at((digests.len() - 4) / 2);
+    assert_eq!(first, second);
+}
+
+#[test]
+fn shutdown_flushes_earlier_replies_and_stops_reading() {
+    let server = Server::new();
+    let mut input = command(&["SET", "k", "v"]);
+    input.extend(command(&["PING"]));
+    input.extend(command(&["SHUTDOWN", "NOSAVE"]));
+    input.extend(command(&["DEL", "k"]));
+    let mut pipe = Pipe::new(&input);
+    let stop = handle_connection(&mut pipe, &server.store, &server.pause, &server.config);
+    assert!(stop);
+    assert_eq!(pipe.output, b"+OK\r\n+PONG\r\n");
+    assert_eq!(server.call(&["EXISTS", "k"]), b":1\r\n");
+}
+
+#[test]
+fn command_info_reports_write_and_readonly_flags() {
+    let server = Server::new();
+    let set = server.call(&["COMMAND", "INFO", "set"]);
+    let get = server.call(&["COMMAND", "INFO", "get"]);
+    let has = |reply: &[u8], flag: &[u8]| reply.windows(flag.len()).any(|w| w == flag);
+    assert!(has(&set, b"$5\r\nwrite\r\n"));
+    assert!(!has(&set, b"$8\r\nreadonly\r\n"));
+    assert!(has(&get, b"$8\r\nreadonly\r\n"));
+    assert!(!has(&get, b"$5\r\nwrite\r\n"));
+}
+
+#[test]
+fn admin_commands_can_be_disabled() {
+    let server = Server::with_config(Config {
+        enable_admin_commands: false,
+        ..Config::default()
+    });
+    let mut input = command(&["DEBUG", "DIGEST"]);
+    input.extend(command(&["CLIENT", "PAUSE", "1000"]));
+    input.extend(command(&["SET", "k", "v"]));
+    input.extend(command(&["GET", "k"]));
+    assert_eq!(
+        server.session(&input).output,
+        b"-NOPERM User default has no permissions to run the 'debug' command\r\n\
+          -NOPERM User default has no permissions to run the 'client' command\r\n\
+          +OK\r\n$1\r\nv\r\n"
+    );
+}
+
+/// Parse the integer in a `:<n>\r\n` reply.
+fn integer(reply: &[u8]) -> i64 {
+    let text = std::str::from_utf8(reply).unwrap();
+    text.strip_prefix(':')
+        .and_then(|text| text.strip_suffix("\r\n"))
+        .and_then(|text| text.parse().ok())
+        .unwrap_or_else(|| panic!("not an integer reply: {:?}", text))
+}
+
+#[test]
+fn memory_usage_grows_with_the_value() {
+    let server = Server::new();
+    server.call(&["SET", "a", "v"]);
+    server.call(&["SET", "b", &"v".repeat(1000)]);
+    let short = integer(&server.call(&["MEMORY", "USAGE", "a"]));
+    let long = integer(&server.call(&["MEMORY", "USAGE", "b"]));
+    assert!(short > 0);
+    assert!(long >= short + 999, "{} vs {}", long, short);
+    assert_eq!(server.call(&["MEMORY", "USAGE", "missing"]), b"$-1\r\n");
+}
+
+#[test]
+fn command_docs_describe_get() {
+    let server = Server::new();
+    assert_eq!(
+        server.call(&["COMMAND", "DOCS", "get"]),
+        b"*2\r\n$3\r\nget\r\n*8\r\n\
+          $7\r\nsummary\r\n$34\r\nReturns the string value of a key.\r\n\
+          $5\r\nsince\r\n$5\r\n1.0.0\r\n\
+          $5\r\ngroup\r\n$6\r\nstring\r\n\
+          $5\r\narity\r\n:2\r\n"
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn ping_over_a_unix_socket() {
+    use std::os::unix::net::{UnixListener, UnixStream};
+
+    let path = std::env::temp_dir().join(format!("redis-test-{}.sock", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let listener = UnixListener::bind(&path).unwrap();
+    let running = Arc::new(AtomicBool::new(true));
+    let acceptor = spawn_serve(listener, Config::default(), &running);
+
+    let mut stream = UnixStream::connect(&path).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    stream.write_all(&command(&["PING"])).unwrap();
+    let mut reply = [0; 7];
+    stream.read_exact(&mut reply).unwrap();
+    assert_eq!(&reply, b"+PONG\r\n");
+
+    drop(stream);
+    running.store(false, Ordering::SeqCst);
+    let _ = UnixStream::connect(&path);
+    acceptor.join().unwrap();
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn non_positive_expire_times_are_rejected() {
+    let server = Server::new();
+    assert_eq!(
+        server.call(&["SET", "k", "v", "PX", "0"]),
+        b"-ERR invalid expire time in 'set' command\r\n"
+    );
+    assert_eq!(server.call(&["EXISTS", "k"]), b":0\r\n");
+    server.call(&["SET", "k", "v"]);
+    assert_eq!(
+        server.call(&["EXPIRE", "k", "-1"]),
+        b"-ERR invalid expire time in 'expire' command\r\n"
+    );
+    assert_eq!(server.call(&["TTL", "k"]), b":-1\r\n");
+}
+
+#[test]
+fn contradictory_set_options_leave_the_key_unchanged() {
+    let server = Server::new();
+    server.call(&["SET", "k", "old"]);
+    for args in [
+        &["SET", "k", "new", "NX", "XX"][..],
+        &["SET", "k", "new", "EX", "10", "PX", "10000"],
+    ] {
+        assert_eq!(server.call(args), b"-ERR syntax error\r\n", "{:?}", args);
+        assert_eq!(server.call(&["GET", "k"]), b"$3\r\nold\r\n");
+        assert_eq!(server.call(&["TTL", "k"]), b":-1\r\n");
+    }
+}
+
+#[test]
+fn connections_share_one_store() {
+    let server = TestServer::start(ephemeral());
+    let mut first = server.connect();
+    let mut second = server.connect();
+    assert_eq!(first.call(&["SET", "k", "v"]), b"+OK\r\n");
+    assert_eq!(second.call(&["GET", "k"]), b"$1\r\nv\r\n");
+    // An idle connection holds no lock, so the other keeps going.
+    assert_eq!(second.call(&["SET", "k", "w"]), b"+OK\r\n");
+    assert_eq!(first.call(&["GET", "k"]), b"$1\r\nw\r\n");
+}
+
+#[test]
+fn incr_keeps_the_ttl() {
+    let server = Server::new();
+    server.call(&["SET", "n", "1", "PX", "10000"]);
+    for _ in 0..5 {
+        server.call(&["INCR", "n"]);
+    }
+    assert_eq!(server.call(&["GET", "n"]), b"$1\r\n6\r\n");
+    let ttl = integer(&server.call(&["PTTL", "n"]));
+    assert!(0 < ttl && ttl <= 10000, "PTTL {}", ttl);
+}
+
+#[test]
+fn getset_returns_the_previous_value() {
+    let server = Server::new();
+    assert_eq!(server.call(&["GETSET", "k", "first"]), b"$-1\r\n");
+    assert_eq!(server.call(&["GETSET", "k", "second"]), b"$5\r\nfirst\r\n");
+    assert_eq!(server.call(&["GET", "k"]), b"$6\r\nsecond\r\n");
+}
+
+#[test]
+fn set_ex_reports_a_ttl_close_to_the_request() {
+    let server = Server::new();
+    assert_eq!(server.call(&["SET", "k", "v", "ex", "100"]), b"+OK\r\n");
+    let ttl = integer(&server.call(&["TTL", "k"]));
+    assert!((99..=100).contains(&ttl), "TTL {}", ttl);
+
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let at = (now + 100).to_string();
+    assert_eq!(server.call(&["SET", "k", "v", "EXAT", &at]), b"+OK\r\n");
+    let ttl = integer(&server.call(&["TTL", "k"]));
+    assert!((98..=100).contains(&ttl), "TTL {}", ttl);
+}
+
+#[test]
+fn idle_clients_are_closed_after_the_timeout() {
+    let server = TestServer::start(Config {
+        timeout: Some(Duration::from_secs(1)),
+        ..ephemeral()
+    });
+    let mut idle = server.connect();
+    let mut active = server.connect();
+    let started = Instant::now();
+    while started.elapsed() < Duration::from_millis(1500) {
+        assert_eq!(active.call(&["PING"]), b"+PONG\r\n");
+        thread::sleep(Duration::from_millis(300));
+    }
+    assert!(idle.is_closed());
+    assert_eq!(active.call(&["PING"]), b"+PONG\r\n");
+}
+
+#[test]
+fn acl_whoami_is_the_default_user() {
+    let server = Server::new();
+    assert_eq!(server.call(&["ACL", "WHOAMI"]), b"$7\r\ndefault\r\n");
+}
+
+#[test]
+fn command_count_drops_when_a_command_is_disabled() {
+    let registered = crate::commands::COMMANDS.len() as i64;
+    let server = Server::new();
+    assert_eq!(integer(&server.call(&["COMMAND", "COUNT"])), registered);
+
+    let mut renamed_commands = HashMap::new();
+    renamed_commands.insert("debug".to_string(), "secret-debug".to_string());
+    renamed_commands.insert("dbsize".to_string(), String::new());
+    let server = Server::with_config(Config {
+        renamed_commands,
+        ..Config::default()
+    });
+    assert_eq!(integer(&server.call(&["COMMAND", "COUNT"])), registered - 1);
+
+    // Every listing leaves out the disabled command and uses the new name.
+    let listed = server.call(&["COMMAND"]);
+    assert!(listed.starts_with(format!("*{}\r\n", registered - 1).as_bytes()));
+    assert_eq!(
+        server.call(&["COMMAND", "INFO", "dbsize", "debug"]),
+        b"*2\r\n$-1\r\n$-1\r\n"
+    );
+    assert!(server
+        .call(&["COMMAND", "INFO", "secret-debug"])
+        .starts_with(b"*1\r\n*6\r\n$12\r\nsecret-debug\r\n"));
+    assert_eq!(server.call(&["COMMAND", "DOCS", "dbsize"]), b"*0\r\n");
+    let docs = server.call(&["COMMAND", "DOCS"]);
+    assert!(docs.starts_with(format!("*{}\r\n", 2 * (registered - 1)).as_bytes()));
+}
+
+#[test]
+fn keys_matches_many_stars_without_backtracking_blowup() {
+    let server = Server::new();
+    server.call(&["SET", &"a".repeat(40), "v"]);
+    server.call(&["SET", "hello*", "v"]);
+    let started = Instant::now();
+    assert_eq!(
+        server.call(&["KEYS", &"*a".repeat(20)]),
+        format!("*1\r\n$40\r\n{}\r\n", "a".repeat(40)).into_bytes()
+    );
+    assert_eq!(
+        server.call(&["KEYS", &format!("{}b", "*a".repeat(20))]),
+        b"*0\r\n"
+    );
+    assert!(started.elapsed() < Duration::from_secs(1));
+
+    for (pattern, matches) in [
+        ("h[a-f]l*\\*", true),
+        ("h[^e]*", false),
+        ("*llo*", true),
+        ("*[e]", false),
+        ("hello\\?", false),
+    ] {
+        let expected: &[u8] = if matches {
+            b"*1\r\n$6\r\nhello*\r\n"
+        } else {
+            b"*0\r\n"
+        };
+        assert_eq!(server.call(&["KEYS", pattern]), expected, "{}", pattern);
+    }
+}
+
+#[test]
+fn frame_split_after_a_carriage_return_waits_for_the_line_feed() {
+    let server = TestServer::start(ephemeral());
+    let mut client = server.connect();
+    client.send(b"*1\r\n+PING\r");
+    thread::sleep(Duration::from_millis(50));
+    client.send(b"\n");
+    assert_eq!(client.reply(), b"+PONG\r\n");
+
+    let output = Server::new().session(b"*1\r\n+PING\rX").output;
+    assert!(output.starts_with(b"-ERR Protocol error"));
+}
+
+#[test]
+fn shutdown_rejects_bad_flags_and_an_impossible_save() {
+    let server = Server::new();
+    let mut input = command(&["SHUTDOWN", "foo"]);
+    input.extend(command(&["SHUTDOWN", "NOSAVE", "extra"]));
+    input.extend(command(&["SHUTDOWN", "SAVE"]));
+    input.extend(command(&["PING"]));
+    let mut pipe = Pipe::new(&input);
+    let stop = handle_connection(&mut pipe, &server.store, &server.pause, &server.config);
+    assert!(!stop);
+    assert_eq!(
+        pipe.output,
+        b"-ERR syntax error\r\n\
+          -ERR syntax error\r\n\
+          -ERR Errors trying to SHUTDOWN. Check logs.\r\n\
+          +PONG\r\n"
+    );
+}
+
+#[test]
+fn blpop_is_woken_by_a_push_from_another_client() {
+    let server = TestServer::start(ephemeral());
+    let mut waiter = server.connect();
+    let mut pusher = server.connect();
+    let blocked = thread::spawn(move || waiter.call(&["BLPOP", "other", "queue", "0"]));
+    // Give the BLPOP time to reach the server and start waiting.
+    thread::sleep(Duration::from_millis(50));
+    assert_eq!(pusher.call(&["LPUSH", "queue", "job"]), b":1\r\n");
+    assert_eq!(
+        blocked.join().unwrap(),
+        b"*2\r\n$5\r\nqueue\r\n$3\r\njob\r\n"
+    );
+    assert_eq!(pusher.call(&["EXISTS", "queue"]), b":0\r\n");
+}
+
+#[test]
+fn blpop_times_out_after_a_fractional_timeout() {
+    let server = TestServer::start(ephemeral());
+    let mut client = server.connect();
+    let started = Instant::now();
+    assert_eq!(client.call(&["BLPOP", "queue", "0.2"]), b"*-1\r\n");
+    let elapsed = started.elapsed();
+    assert!(
+        (Duration::from_millis(200)..Duration::from_secs(2)).contains(&elapsed),
+        "BLPOP returned after {:?}",
+        elapsed
+    );
+}
+
+#[test]
+fn blpop_on_a_non_list_key_is_wrongtype() {
+    let server = TestServer::start(ephemeral());
+    let mut client = server.connect();
+    client.call(&["SET", "k", "v"]);
+    assert_eq!(
+        client.call(&["BLPOP", "k", "1"]),
+        b"-WRONGTYPE Operation against a key holding the wrong kind of value\r\n"
+    );
+}
+
+#[test]
+fn debug_digest_matches_for_identical_datasets() {
+    let commands: [&[&str]; 4] = [
+        &["SET", "s", "v"],
+        &["RPUSH", "l", "a", "b"],
+        &["HSET", "h", "f", "v"],
+        &["SET", "t", "v", "EX", "100"],
+    ];
+    let first = Server::new();
+    let second = Server::new();
+    for args in commands {
+        first.call(args);
+    }
+    for args in commands.iter().rev() {
+        second.call(args);
+    }
+    let digest = first.call(&["DEBUG", "DIGEST"]);
+    assert_eq!(digest, second.call(&["DEBUG", "DIGEST"]));
+    assert_ne!(digest, b"$16\r\n0000000000000000\r\n");
+
+    second.call(&["DEL", "l"]);
+    assert_ne!(digest, second.call(&["DEBUG", "DIGEST"]));
+}
+
+#[test]
+fn commands_for_one_type_reject_keys_of_another() {
+    type Commands<'a> = &'a [&'a [&'a str]];
+    let string: Commands = &[
+        &["GET", "k"],
+        &["GETSET", "k", "v"],
+        &["APPEND", "k", "v"],
+        &["STRLEN", "k"],
+        &["INCR", "k"],
+        &["DECR", "k"],
+        &["INCRBY", "k", "1"],
+        &["DECRBY", "k", "1"],
+        &["GETRANGE", "k", "0", "-1"],
+        &["SETRANGE", "k", "0", "v"],
+    ];
+    let list: Commands = &[
+        &["LPUSH", "k", "v"],
+        &["RPUSH", "k", "v"],
+        &["LPOP", "k"],
+        &["RPOP", "k"],
+        &["LLEN", "k"],
+        &["LRANGE", "k", "0", "-1"],
+        &["BLPOP", "k", "0.1"],
+    ];
+    let hash: Commands = &[
+        &["HSET", "k", "f", "v"],
+        &["HGET", "k", "f"],
+        &["HGETALL", "k"],
+        &["HDEL", "k", "f"],
+        &["HEXISTS", "k", "f"],
+        &["HLEN", "k"],
+    ];
+    let types: [(&str, &[&str], Commands); 3] = [
+        ("string", &["SET", "k", "v"], string),
+        ("list", &["RPUSH", "k", "v"], list),
+        ("hash", &["HSET", "k", "f", "v"], hash),
+    ];
+    for (kind, create, _) in types {
+        let server = Server::new();
+        server.call(create);
+        for (other, _, commands) in types {
+            if other == kind {
+                continue;
+            }
+            for args in commands {
+                assert_eq!(
+                    server.call(args),
+                    b"-WRONGTYPE Operation against a key holding the wrong kind of value\r\n",
+                    "{:?} on a {}",
+                    args,
+                    kind
+                );
+            }
+        }
+        assert_eq!(
+            server.call(&["TYPE", "k"]),
+            format!("+{}\r\n", kind).into_bytes()
+        );
+    }
+}